@@ -1,8 +1,22 @@
 use std::{env, fs, io::Write, path::PathBuf};
 
 use xbpatch_core::{
+    file_handling::LiveFile,
+    game_files::GameFiles,
     iso_handling::{self, backup_file, restore_backup},
-    patching::{Patch, PatchEntry, PatchOffsetType},
+    patching::{
+        PatchEntry, PatchSet,
+        bundle::{self, PatchBundle},
+        include,
+        job::PatchJob,
+        patch_set::{self, OptionGroupSelection},
+        schema,
+        selection::{self, SelectionItem, SetSelection},
+        selection_code,
+        signing::{self, Keyring, SigningKeyFile, Trust},
+        target::TargetMatch,
+        translation, validation,
+    },
     xbe::{PatchReport, XBEWriter},
 };
 
@@ -11,7 +25,21 @@ use walkdir::WalkDir;
 #[derive(Debug, Default)]
 struct XBPatchArgs {
     iso_path: Option<PathBuf>,
-    config_path: Option<PathBuf>,
+    config_paths: Vec<PathBuf>,
+    fill_original_bytes_path: Option<PathBuf>,
+    upgrade_schema_path: Option<PathBuf>,
+    json_schema_path: Option<PathBuf>,
+    validate_paths: Vec<PathBuf>,
+    create_bundle_path: Option<PathBuf>,
+    xbe_path: Option<PathBuf>,
+    language: Option<String>,
+    generate_key_path: Option<PathBuf>,
+    sign_path: Option<PathBuf>,
+    key_path: Option<PathBuf>,
+    keyring_path: Option<PathBuf>,
+    seed: Option<String>,
+    selection_code: Option<String>,
+    allow_tampered: bool,
     unexpected_args: Vec<String>,
 }
 
@@ -20,6 +48,19 @@ enum ArgParseState {
     NoState,
     ExpectingFilepath,
     ExpectingConfigpath,
+    ExpectingFillOriginalBytesPath,
+    ExpectingUpgradeSchemaPath,
+    ExpectingJsonSchemaPath,
+    ExpectingValidatePath,
+    ExpectingCreateBundlePath,
+    ExpectingXbePath,
+    ExpectingLanguage,
+    ExpectingGenerateKeyPath,
+    ExpectingSignPath,
+    ExpectingKeyPath,
+    ExpectingKeyringPath,
+    ExpectingSeed,
+    ExpectingSelectionCode,
     UnexpectedArg,
 }
 
@@ -30,7 +71,14 @@ enum ArgError {
 }
 
 // Usage
-// xbpatch gbtg.iso --config gbtg.xbconf
+// xbpatch gbtg.iso --config ghoulies.json [--config another_set.json] [--language ja] [--keyring keyring.json] [--seed 1234] [--selection-code XBP1-...] [--allow-tampered]
+// xbpatch --fill-original-bytes ghoulies.json --xbe default.xbe
+// xbpatch --upgrade-schema ghoulies.json
+// xbpatch --write-json-schema xbpatchset.schema.json
+// xbpatch --validate ghoulies.json [--validate ghoulies.xbpatch] [--keyring keyring.json]
+// xbpatch --create-bundle ghoulies.json
+// xbpatch --generate-key hethan.key
+// xbpatch --sign ghoulies.json --key hethan.key
 
 fn main() {
     // Parse args
@@ -46,6 +94,170 @@ fn main() {
         },
     };
 
+    if let Some(patch_set_path) = args.fill_original_bytes_path.take() {
+        let xbe_path = match args.xbe_path.take() {
+            Some(p) => p,
+            None => error_exit("A clean .xbe must be specified with --xbe."),
+        };
+
+        fill_original_bytes(&patch_set_path, &xbe_path);
+        return;
+    }
+
+    if let Some(patch_set_path) = args.create_bundle_path.take() {
+        create_bundle(&patch_set_path);
+        return;
+    }
+
+    if let Some(key_path) = args.generate_key_path.take() {
+        generate_key(&key_path);
+        return;
+    }
+
+    if let Some(patch_set_path) = args.sign_path.take() {
+        let key_path = match args.key_path.take() {
+            Some(p) => p,
+            None => error_exit("A key to sign with must be specified with --key."),
+        };
+
+        sign_patch_set(&patch_set_path, &key_path);
+        return;
+    }
+
+    // Without a keyring, signed patch sets can be checked for tampering but not trusted
+    let keyring = match &args.keyring_path {
+        Some(path) => match Keyring::from_file(path) {
+            Ok(k) => k,
+            Err(e) => error_exit_with_details(
+                format!("Unable to load keyring {}", path.display()),
+                e.to_string(),
+            ),
+        },
+        None => Keyring::default(),
+    };
+
+    if !args.validate_paths.is_empty() {
+        validate_patch_sets(&args.validate_paths, &keyring);
+        return;
+    }
+
+    if let Some(schema_path) = args.json_schema_path.take() {
+        write_json_schema(&schema_path);
+        return;
+    }
+
+    if let Some(patch_set_path) = args.upgrade_schema_path.take() {
+        upgrade_schema(&patch_set_path);
+        return;
+    }
+
+    if args.config_paths.is_empty() {
+        error_exit("At least one patch set must be specified with --config.");
+    }
+
+    // Names and descriptions are shown in the user's language where the patch set has translations
+    let language = args.language.take().or_else(translation::user_language);
+    let language = language.as_deref();
+
+    let mut patch_sets = Vec::new();
+
+    for config_path in &args.config_paths {
+        let patch_set = match include::load_with_includes(config_path) {
+            Ok(ps) => ps,
+            Err(e) => error_exit_with_details(
+                format!("Unable to load patch set {}", config_path.display()),
+                e.to_string(),
+            ),
+        };
+
+        let trust = signing::verify_patch_set(config_path, &keyring);
+        println!("\n{}: {}", config_path.display(), trust);
+
+        if let Trust::Tampered(_) = trust {
+            if !args.allow_tampered {
+                error_exit(
+                    "This patch set has been changed since it was signed, so it won't be used. Use --allow-tampered to use it anyway.",
+                );
+            }
+
+            println!("WARNING: Using this patch set anyway, since --allow-tampered was given.");
+        }
+
+        patch_sets.push(patch_set);
+    }
+
+    // A selection code chooses the entries and their values instead of asking for them
+    let chosen: Vec<SetSelection> = match args.selection_code.take() {
+        Some(code) => {
+            let loaded: Vec<&PatchSet> = patch_sets.iter().collect();
+
+            match selection_code::decode(&code, &loaded) {
+                Ok(s) => s,
+                Err(e) => error_exit_with_details("Unable to use the selection code", e),
+            }
+        }
+        None => patch_sets
+            .iter()
+            .map(|patch_set| {
+                let mut selection = prompt_entry_selection(patch_set, language);
+                prompt_parameter_values(patch_set, &mut selection, language);
+                selection
+            })
+            .collect(),
+    };
+
+    let patch_sets: Vec<(PatchSet, SetSelection)> = patch_sets.into_iter().zip(chosen).collect();
+
+    let selections: Vec<(&PatchSet, &SetSelection)> = patch_sets
+        .iter()
+        .map(|(patch_set, selection)| (patch_set, selection))
+        .collect();
+
+    println!(
+        "\nSelection code: {}\nUse --selection-code to choose the same entries again.",
+        selection_code::encode(&selections)
+    );
+
+    // Giving the seed from an earlier run picks the same random values again
+    let job = match args.seed.take() {
+        Some(seed) => match seed.parse::<u64>() {
+            Ok(seed) => PatchJob::with_seed(&selections, seed),
+            Err(_) => error_exit(format!("The seed \"{}\" is not a whole number.", seed)),
+        },
+        None => PatchJob::new(&selections),
+    };
+
+    let (mut patch_entries, seed) = match job {
+        Ok(job) => {
+            let seed = job.seed();
+            (job.into_entries(), seed)
+        }
+        Err(errors) => {
+            for e in &errors {
+                eprintln!("{}", e);
+            }
+            error_exit("The chosen entries can't be applied together.");
+        }
+    };
+
+    let broken: Vec<&PatchEntry> = patch_entries
+        .iter()
+        .filter(|e| e.status().is_broken())
+        .collect();
+
+    for entry in &broken {
+        println!(
+            "WARNING: \"{}\" is marked as \"{}\".",
+            entry.localised_name(language),
+            entry.status()
+        );
+    }
+
+    if !broken.is_empty() && !prompt_user_bool("Would you like to continue anyway?".to_string()) {
+        println!("Exiting now.");
+        std::process::exit(0);
+    }
+
     // TODO: Make this a program argument
     let extract_xiso_path = PathBuf::from("extract-xiso");
 
@@ -60,6 +272,9 @@ fn main() {
 
     let extraction_dir: PathBuf = "./xbpatch_temp/isos/isoname".into();
 
+    // Kept outside the extracted game, so they aren't packed into the new iso
+    let backup_dir: PathBuf = "./xbpatch_temp/backups/isoname".into();
+
     // If the folder exists and the user wants to delete it, then delete it
     if extraction_dir.exists() && extraction_dir.is_dir() {
         let skip_extraction = prompt_user_bool(format!(
@@ -68,6 +283,10 @@ fn main() {
         ));
         if !skip_extraction {
             std::fs::remove_dir_all(&extraction_dir).expect("Unable to delete existing folder.");
+
+            if backup_dir.exists() {
+                std::fs::remove_dir_all(&backup_dir).expect("Unable to delete existing backups.");
+            }
         }
     }
 
@@ -98,7 +317,7 @@ fn main() {
     }
 
     // Parse the config
-    let mut xbe_writer = match XBEWriter::new(&xbe_path) {
+    let xbe_writer = match XBEWriter::new(&xbe_path) {
         Ok(w) => w,
         Err(_) => error_exit(format!(
             "Unable to open file {} for writing.",
@@ -106,33 +325,84 @@ fn main() {
         )),
     };
 
-    let mut patch_entries = Vec::new();
-    patch_entries.push(PatchEntry::new(
-        String::from("Uncap frame rate"),
-        String::from("Uncaps the frame rate"),
-        None,
-        vec![
-            Patch {
-                offset: 0x154919,
-                offset_type: PatchOffsetType::Virtual,
-                replacement_bytes: vec![0xeb, 0x21],
-                original_bytes: None,
-            },
-            Patch {
-                offset: 0x81454,
-                offset_type: PatchOffsetType::Virtual,
-                replacement_bytes: vec![0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90],
-                original_bytes: None,
-            },
-        ],
-    ));
+    let identity = match xbe_writer.identify() {
+        Ok(i) => i,
+        Err(e) => error_exit_with_details("Unable to read default.xbe", e.to_string()),
+    };
+
+    for (patch_set, selection) in &patch_sets {
+        if !selection.enabled.contains(&true) {
+            continue;
+        }
+
+        match patch_set.check_targets(&identity) {
+            TargetMatch::Matches => (),
+            TargetMatch::UnknownBuild(reason) => {
+                println!("WARNING: {}.", reason);
+
+                if !prompt_user_bool("Would you like to continue anyway?".to_string()) {
+                    println!("Exiting now.");
+                    std::process::exit(0);
+                }
+            }
+            TargetMatch::WrongGame(reason) => {
+                error_exit(format!("{}. Refusing to patch a different game.", reason))
+            }
+        }
+    }
 
     let mut report = PatchReport::default();
+    report.set_seed(seed);
+
+    // Entries with nothing to apply to this build are left out, and count as failures
+    patch_entries.retain_mut(|entry| match entry.select_build(&identity) {
+        Ok(true) => true,
+        Ok(false) => {
+            println!(
+                "WARNING: \"{}\" has no patches specific to this build of the game, so its usual patches will be used.",
+                entry.localised_name(language)
+            );
+            true
+        }
+        Err(e) => {
+            println!("FAILED! {}", e);
+            report.add_failure();
+            false
+        }
+    });
+
+    let mut game_files = match GameFiles::open(&xbe_path, xbe_writer, &patch_entries, &backup_dir) {
+        Ok(g) => g,
+        Err(e) => error_exit_with_details("Unable to open the game files to patch", e.to_string()),
+    };
+
+    match game_files.find_overlaps(&patch_entries) {
+        Ok(overlaps) => {
+            for overlap in &overlaps {
+                println!("WARNING: {}.", overlap);
+            }
+
+            if !overlaps.is_empty()
+                && !prompt_user_bool(
+                    "Some entries overwrite each other, and the later entry will win. Would you like to continue?"
+                        .to_string(),
+                )
+            {
+                println!("Exiting now.");
+                std::process::exit(0);
+            }
+        }
+        Err(e) => error_exit_with_details("Unable to resolve patch offsets", e.to_string()),
+    };
+
+    if let Err(e) = game_files.apply_file_changes() {
+        error_exit_with_details("Unable to change the game's files", e.to_string());
+    }
 
     for entry in patch_entries {
-        print!("Applying patch \"{}\"...  ", entry.name());
+        print!("Applying patch \"{}\"...  ", entry.localised_name(language));
 
-        match xbe_writer.apply_patches(&entry) {
+        match game_files.apply_patches(&entry) {
             Ok(patch_report) => {
                 if patch_report.patch_successful() {
                     report.add_success();
@@ -140,7 +410,10 @@ fn main() {
                     println!("DONE!");
                 } else {
                     report.add_failure();
-                    println!("FAILED!\n        Failed to apply {}.", entry.name());
+                    println!(
+                        "FAILED!\n        Failed to apply {}.",
+                        entry.localised_name(language)
+                    );
                 }
             }
             Err(e) => {
@@ -149,6 +422,13 @@ fn main() {
         }
     }
 
+    if let Some(seed) = report.seed() {
+        println!(
+            "Random values were picked with seed {}. Use --seed {} to make this build again.",
+            seed, seed
+        );
+    }
+
     if report.failures() == 0 {
         println!("All patches applied successfully.");
     } else {
@@ -221,6 +501,220 @@ fn prompt_user_bool(msg: String) -> bool {
     user_input.to_lowercase().starts_with("y")
 }
 
+/// Lists the entries of a patch set and asks the user which ones to enable. Each option group is
+/// listed once, and its option is asked for separately.
+fn prompt_entry_selection(patch_set: &PatchSet, language: Option<&str>) -> SetSelection {
+    println!(
+        "\nPatch set \"{}\" v{}.{} by {}",
+        patch_set.localised_name(language),
+        patch_set.version_major,
+        patch_set.version_minor,
+        patch_set.author
+    );
+
+    let categories = selection::categorised_selection_items(patch_set);
+    let has_headings = categories.iter().any(|c| c.name.is_some());
+
+    let mut items = Vec::new();
+
+    for category in categories {
+        if has_headings {
+            println!("\n {}", category.name.as_deref().unwrap_or("Other"));
+        }
+
+        for item in category.items {
+            let n = items.len() + 1;
+
+            match &item {
+                SelectionItem::Entry(i) => {
+                    println!(
+                        "  {}) {}",
+                        n,
+                        entry_summary(&patch_set.entries[*i], language)
+                    )
+                }
+                SelectionItem::Group { group, options } => println!(
+                    "  {}) {} (choose one of {} options)",
+                    n,
+                    group.name,
+                    options.len()
+                ),
+            }
+
+            items.push(item);
+        }
+    }
+
+    let chosen = prompt_user_numbers(
+        "Enter the numbers of the entries to enable, separated by spaces",
+        items.len(),
+    );
+
+    let mut selection = SetSelection::new(patch_set);
+    let enabled = &mut selection.enabled;
+
+    for item in chosen.iter().map(|n| &items[n - 1]) {
+        if let SelectionItem::Entry(i) = item {
+            enabled[*i] = true;
+        }
+    }
+
+    // Groups which must have an option are asked for whenever the patch set is used
+    let in_use = enabled.iter().any(|e| *e);
+
+    for (n, item) in items.iter().enumerate() {
+        let SelectionItem::Group { group, options } = item else {
+            continue;
+        };
+
+        let required = in_use && group.selection == OptionGroupSelection::ExactlyOne;
+        if !chosen.contains(&(n + 1)) && !required {
+            continue;
+        }
+
+        println!("\n{}", group.name);
+        for (k, i) in options.iter().enumerate() {
+            println!(
+                "  {}) {}",
+                k + 1,
+                entry_summary(&patch_set.entries[*i], language)
+            );
+        }
+
+        loop {
+            let choice = prompt_user_numbers("Choose an option", options.len());
+
+            if let [k] = choice[..] {
+                enabled[options[k - 1]] = true;
+                break;
+            }
+
+            println!("Please enter exactly one option.");
+        }
+    }
+
+    selection
+}
+
+// An entry's name, along with its credits, tags and status
+fn entry_summary(entry: &PatchEntry, language: Option<&str>) -> String {
+    let mut summary = entry.localised_name(language).to_string();
+
+    if let Some(version) = entry.version() {
+        summary.push_str(&format!(" v{}", version));
+    }
+
+    if let Some(author) = entry.alt_author() {
+        summary.push_str(&format!(" by {}", author));
+    }
+
+    if let Some(file) = entry.included_from().and_then(|p| p.file_name()) {
+        summary.push_str(&format!(" (from {})", file.to_string_lossy()));
+    }
+
+    if !entry.tags().is_empty() {
+        summary.push_str(&format!(" [{}]", entry.tags().join(", ")));
+    }
+
+    if !entry.status().is_stable() {
+        summary.push_str(&format!(" ({})", entry.status()));
+    }
+
+    for issue in entry.known_issues() {
+        summary.push_str(&format!("\n       Known issue: {}", issue));
+    }
+
+    summary
+}
+
+/// Asks for the value of every parameter of the enabled entries. Leaving a value empty keeps its
+/// default.
+fn prompt_parameter_values(
+    patch_set: &PatchSet,
+    selection: &mut SetSelection,
+    language: Option<&str>,
+) {
+    for (i, entry) in patch_set.entries.iter().enumerate() {
+        if !selection.is_enabled(i) || entry.parameters().is_empty() {
+            continue;
+        }
+
+        println!(
+            "
+{}",
+            entry.localised_name(language)
+        );
+
+        for parameter in entry.parameters() {
+            let unit = parameter.unit.as_deref().unwrap_or_default();
+            let range = match (parameter.min, parameter.max) {
+                (Some(min), Some(max)) => format!(", {}{} to {}{}", min, unit, max, unit),
+                (Some(min), None) => format!(", at least {}{}", min, unit),
+                (None, Some(max)) => format!(", at most {}{}", max, unit),
+                (None, None) => String::new(),
+            };
+
+            loop {
+                print!(
+                    "  {} [default {}{}{}]: ",
+                    parameter.name, parameter.default, unit, range
+                );
+                std::io::stdout().flush().expect("Unable to flush stdout.");
+
+                let mut user_input = String::new();
+                std::io::stdin()
+                    .read_line(&mut user_input)
+                    .expect("Failed to read line");
+
+                let user_input = user_input.trim().trim_end_matches(unit).trim();
+                if user_input.is_empty() {
+                    break;
+                }
+
+                let value = match user_input.parse::<f64>() {
+                    Ok(v) => v,
+                    Err(_) => {
+                        println!("  Please enter a number.");
+                        continue;
+                    }
+                };
+
+                match parameter.encode(value) {
+                    Ok(_) => {
+                        selection.set_value(i, &parameter.name, value);
+                        break;
+                    }
+                    Err(reason) => println!("  {}.", reason),
+                }
+            }
+        }
+    }
+}
+
+/// Asks the user for a list of numbers between 1 and `max`, repeating until the input is valid.
+fn prompt_user_numbers(msg: &str, max: usize) -> Vec<usize> {
+    loop {
+        print!("{}: ", msg);
+        std::io::stdout().flush().expect("Unable to flush stdout.");
+
+        let mut user_input = String::new();
+        std::io::stdin()
+            .read_line(&mut user_input)
+            .expect("Failed to read line");
+
+        let numbers: Result<Vec<usize>, _> = user_input
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<usize>())
+            .collect();
+
+        match numbers {
+            Ok(n) if n.iter().all(|n| (1..=max).contains(n)) => return n,
+            _ => println!("Please enter numbers between 1 and {}.", max),
+        }
+    }
+}
+
 fn parse_args(args: env::Args) -> Result<XBPatchArgs, ArgError> {
     let mut state: ArgParseState = ArgParseState::NoState;
     let mut ret_args: XBPatchArgs = Default::default();
@@ -232,6 +726,34 @@ fn parse_args(args: env::Args) -> Result<XBPatchArgs, ArgError> {
                     if arg.starts_with("--") {
                         if arg == "--config" {
                             state = ArgParseState::ExpectingConfigpath;
+                        } else if arg == "--fill-original-bytes" {
+                            state = ArgParseState::ExpectingFillOriginalBytesPath;
+                        } else if arg == "--upgrade-schema" {
+                            state = ArgParseState::ExpectingUpgradeSchemaPath;
+                        } else if arg == "--write-json-schema" {
+                            state = ArgParseState::ExpectingJsonSchemaPath;
+                        } else if arg == "--validate" {
+                            state = ArgParseState::ExpectingValidatePath;
+                        } else if arg == "--create-bundle" {
+                            state = ArgParseState::ExpectingCreateBundlePath;
+                        } else if arg == "--xbe" {
+                            state = ArgParseState::ExpectingXbePath;
+                        } else if arg == "--language" {
+                            state = ArgParseState::ExpectingLanguage;
+                        } else if arg == "--generate-key" {
+                            state = ArgParseState::ExpectingGenerateKeyPath;
+                        } else if arg == "--sign" {
+                            state = ArgParseState::ExpectingSignPath;
+                        } else if arg == "--key" {
+                            state = ArgParseState::ExpectingKeyPath;
+                        } else if arg == "--keyring" {
+                            state = ArgParseState::ExpectingKeyringPath;
+                        } else if arg == "--seed" {
+                            state = ArgParseState::ExpectingSeed;
+                        } else if arg == "--selection-code" {
+                            state = ArgParseState::ExpectingSelectionCode;
+                        } else if arg == "--allow-tampered" {
+                            ret_args.allow_tampered = true;
                         } else {
                             state = ArgParseState::UnexpectedArg;
                             ret_args.unexpected_args.push(arg);
@@ -250,7 +772,59 @@ fn parse_args(args: env::Args) -> Result<XBPatchArgs, ArgError> {
                 state = ArgParseState::NoState
             }
             ArgParseState::ExpectingConfigpath => {
-                ret_args.config_path = Some(arg.into());
+                ret_args.config_paths.push(arg.into());
+                state = ArgParseState::NoState
+            }
+            ArgParseState::ExpectingFillOriginalBytesPath => {
+                ret_args.fill_original_bytes_path = Some(arg.into());
+                state = ArgParseState::NoState
+            }
+            ArgParseState::ExpectingUpgradeSchemaPath => {
+                ret_args.upgrade_schema_path = Some(arg.into());
+                state = ArgParseState::NoState
+            }
+            ArgParseState::ExpectingJsonSchemaPath => {
+                ret_args.json_schema_path = Some(arg.into());
+                state = ArgParseState::NoState
+            }
+            ArgParseState::ExpectingValidatePath => {
+                ret_args.validate_paths.push(arg.into());
+                state = ArgParseState::NoState
+            }
+            ArgParseState::ExpectingCreateBundlePath => {
+                ret_args.create_bundle_path = Some(arg.into());
+                state = ArgParseState::NoState
+            }
+            ArgParseState::ExpectingXbePath => {
+                ret_args.xbe_path = Some(arg.into());
+                state = ArgParseState::NoState
+            }
+            ArgParseState::ExpectingLanguage => {
+                ret_args.language = Some(arg);
+                state = ArgParseState::NoState
+            }
+            ArgParseState::ExpectingGenerateKeyPath => {
+                ret_args.generate_key_path = Some(arg.into());
+                state = ArgParseState::NoState
+            }
+            ArgParseState::ExpectingSignPath => {
+                ret_args.sign_path = Some(arg.into());
+                state = ArgParseState::NoState
+            }
+            ArgParseState::ExpectingKeyPath => {
+                ret_args.key_path = Some(arg.into());
+                state = ArgParseState::NoState
+            }
+            ArgParseState::ExpectingKeyringPath => {
+                ret_args.keyring_path = Some(arg.into());
+                state = ArgParseState::NoState
+            }
+            ArgParseState::ExpectingSeed => {
+                ret_args.seed = Some(arg);
+                state = ArgParseState::NoState
+            }
+            ArgParseState::ExpectingSelectionCode => {
+                ret_args.selection_code = Some(arg);
                 state = ArgParseState::NoState
             }
             ArgParseState::UnexpectedArg => state = ArgParseState::NoState,
@@ -264,23 +838,187 @@ fn parse_args(args: env::Args) -> Result<XBPatchArgs, ArgError> {
     }
 }
 
-/*
-let patches = vec![GamePatch {
-    name: String::from("Force 1sec cutscenes"),
-    offset: 0x4d5ac,
-    offset_type: GamePatchOffsetType::Virtual,
-    replacement_bytes: vec![0x90, 0x90, 0x90, 0x90, 0x90, 0x90],
-    original_bytes: Some(vec![0xf3, 0x0f, 0x10, 0x3c, 0x24, 0x08]),
-}];
-
-let patches = vec![GamePatch {
-    name: String::from("Remove celebration"),
-    offset: 0x2bb36,
-    offset_type: GamePatchOffsetType::Virtual,
-    replacement_bytes: vec![0x90, 0x90, 0x90, 0x90, 0x90, 0x90],
-    original_bytes: None,
-}];
-*/
+fn fill_original_bytes(patch_set_path: &PathBuf, xbe_path: &PathBuf) {
+    let mut file = match LiveFile::<PatchSet>::from_existing(patch_set_path) {
+        Ok(f) => f,
+        Err(e) => error_exit_with_details(
+            format!("Unable to load patch set {}", patch_set_path.display()),
+            e.to_string(),
+        ),
+    };
+
+    match patch_set::fill_original_bytes(&mut file, xbe_path) {
+        Ok(filled) => println!(
+            "Filled original bytes for {} patches in {}.",
+            filled,
+            patch_set_path.display()
+        ),
+        Err(e) => error_exit_with_details(
+            format!("Unable to fill original bytes from {}", xbe_path.display()),
+            e.to_string(),
+        ),
+    };
+}
+
+/// Rewrites a patch set file in the newest schema version.
+fn upgrade_schema(patch_set_path: &PathBuf) {
+    let mut file = match LiveFile::<PatchSet>::from_existing(patch_set_path) {
+        Ok(f) => f,
+        Err(e) => error_exit_with_details(
+            format!("Unable to load patch set {}", patch_set_path.display()),
+            e.to_string(),
+        ),
+    };
+
+    match file.save() {
+        Ok(_) => println!(
+            "Upgraded {} to schema version {}.",
+            patch_set_path.display(),
+            schema::CURRENT_SCHEMA
+        ),
+        Err(e) => error_exit_with_details(
+            format!("Unable to save patch set {}", patch_set_path.display()),
+            e.to_string(),
+        ),
+    };
+}
+
+/// Prints every problem found in each patch set or bundle, and exits with an error if there were any.
+fn validate_patch_sets(patch_set_paths: &[PathBuf], keyring: &Keyring) {
+    let mut valid = true;
+
+    for path in patch_set_paths {
+        let is_bundle = path
+            .extension()
+            .is_some_and(|e| e == bundle::BUNDLE_EXTENSION);
+
+        let problems = if is_bundle {
+            PatchBundle::open(path).and_then(|mut b| {
+                let problems = b.validate()?;
+                Ok((problems, b.verify(keyring)?))
+            })
+        } else {
+            validation::validate_file(path)
+                .map(|problems| (problems, signing::verify_patch_set(path, keyring)))
+        };
+
+        // A tampered signature is a problem, but an unsigned or untrusted one is only reported
+        if let Ok((_, trust)) = &problems {
+            println!("{}: {}", path.display(), trust);
+
+            if let Trust::Tampered(_) = trust {
+                valid = false;
+            }
+        }
+
+        let problems = problems.map(|(problems, _)| problems);
+
+        match problems {
+            Ok(problems) if problems.is_empty() => println!("{}: OK", path.display()),
+            Ok(problems) => {
+                valid = false;
+
+                println!("{}: {} problem(s)", path.display(), problems.len());
+                for problem in &problems {
+                    println!("    {}", problem);
+                }
+            }
+            Err(e) => {
+                valid = false;
+                println!("{}: Unable to read the file. {}", path.display(), e);
+            }
+        }
+    }
+
+    if !valid {
+        std::process::exit(1);
+    }
+}
+
+/// Packs a patch set and the files it copies into the game into a bundle next to the patch set.
+fn create_bundle(patch_set_path: &PathBuf) {
+    let bundle_path = patch_set_path.with_extension(bundle::BUNDLE_EXTENSION);
+
+    match bundle::create_bundle(patch_set_path, &bundle_path) {
+        Ok(manifest) => println!(
+            "Bundled {} file(s) into {}.",
+            manifest.files.len(),
+            bundle_path.display()
+        ),
+        Err(e) => error_exit_with_details(
+            format!("Unable to bundle {}", patch_set_path.display()),
+            e.to_string(),
+        ),
+    };
+}
+
+/// Makes a new key for signing patch sets, named after its file.
+fn generate_key(key_path: &PathBuf) {
+    if key_path.exists() {
+        error_exit(format!(
+            "{} already exists. Refusing to overwrite a key.",
+            key_path.display()
+        ));
+    }
+
+    let name = key_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let trusted_key = SigningKeyFile::generate(name)
+        .and_then(|key| {
+            fs::write(key_path, serde_json::to_vec_pretty(&key)?)?;
+            key.trusted_key()
+        })
+        .unwrap_or_else(|e| error_exit_with_details("Unable to generate a key", e.to_string()));
+
+    println!(
+        "Wrote a new key to {}. Keep it private, since anyone with it can sign patch sets as you.",
+        key_path.display()
+    );
+    println!(
+        "To trust your patch sets, others can add this to the \"trusted\" list of their keyring:\n{}",
+        serde_json::to_string_pretty(&trusted_key).expect("Unable to serialize the public key.")
+    );
+}
+
+/// Signs a patch set and its files, writing the signature next to the patch set.
+fn sign_patch_set(patch_set_path: &PathBuf, key_path: &PathBuf) {
+    let signature = SigningKeyFile::from_file(key_path)
+        .and_then(|key| signing::sign_patch_set(patch_set_path, &key))
+        .unwrap_or_else(|e| {
+            error_exit_with_details(
+                format!("Unable to sign {}", patch_set_path.display()),
+                e.to_string(),
+            )
+        });
+
+    println!(
+        "Signed {} file(s) as \"{}\", and wrote the signature to {}.",
+        signature.files.len(),
+        signature.signer,
+        signing::signature_path(patch_set_path).display()
+    );
+}
+
+/// Writes the JSON Schema for patch set files, which editors can use to check patch sets.
+fn write_json_schema(schema_path: &PathBuf) {
+    let mut contents = serde_json::to_string_pretty(&schema::json_schema())
+        .expect("Unable to serialize the JSON Schema.");
+    contents.push('\n');
+
+    match fs::write(schema_path, contents) {
+        Ok(_) => println!(
+            "Wrote the patch set JSON Schema to {}.",
+            schema_path.display()
+        ),
+        Err(e) => error_exit_with_details(
+            format!("Unable to write {}", schema_path.display()),
+            e.to_string(),
+        ),
+    };
+}
 
 fn error_exit_with_details(message: impl AsRef<str>, details: impl AsRef<str>) -> ! {
     eprintln!("Unable to continue.");
@@ -0,0 +1,583 @@
+use std::collections::{HashMap, HashSet};
+
+use iced_x86::{
+    BlockEncoder, BlockEncoderOptions, Code, Encoder, EncodingKind, Instruction, InstructionBlock,
+    Mnemonic, OpCodeOperandKind, OpKind, Register,
+};
+
+// Alternative names for conditional jumps, mapped to the names iced uses
+const JCC_ALIASES: [(&str, &str); 14] = [
+    ("jz", "je"),
+    ("jnz", "jne"),
+    ("jc", "jb"),
+    ("jnae", "jb"),
+    ("jnc", "jae"),
+    ("jnb", "jae"),
+    ("jna", "jbe"),
+    ("jnbe", "ja"),
+    ("jnge", "jl"),
+    ("jnl", "jge"),
+    ("jng", "jle"),
+    ("jnle", "jg"),
+    ("jpe", "jp"),
+    ("jpo", "jnp"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Memory {
+    // Size in bytes given by eg. "dword ptr"
+    size: Option<usize>,
+    segment: Register,
+    base: Register,
+    index: Register,
+    scale: u32,
+    displacement: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Register(Register),
+    Memory(Memory),
+    Immediate(i64),
+    Label(String),
+}
+
+/// Assembles 32 bit x86 code written in Intel syntax, as though it was placed at `address`. Each
+/// line holds one instruction, optionally preceded by a label (eg. "skip: nop"). Anything after a
+/// ';' is a comment.
+pub fn assemble(lines: &[String], address: u32) -> Result<Vec<u8>, String> {
+    let mnemonics: HashMap<String, Mnemonic> = Mnemonic::values()
+        .map(|m| (format!("{:?}", m).to_lowercase(), m))
+        .collect();
+
+    // Each label is found by the index of the instruction it labels
+    let mut label_indices: HashMap<String, usize> = HashMap::new();
+    let mut parsed = Vec::new();
+
+    for (n, line) in lines.iter().enumerate() {
+        let line = line.split(';').next().unwrap_or_default().trim();
+        let error = |e: String| format!("Line {} \"{}\": {}", n + 1, line, e);
+
+        let (label, instruction) = match line.split_once(':') {
+            Some((label, rest)) if is_identifier(label.trim()) => (Some(label.trim()), rest.trim()),
+            _ => (None, line),
+        };
+
+        // Several labels in a row all refer to the same instruction
+        if let Some(label) = label
+            && label_indices
+                .insert(label.to_lowercase(), parsed.len())
+                .is_some()
+        {
+            return Err(error(format!("The label \"{}\" is defined twice", label)));
+        }
+
+        if instruction.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = parse_instruction(instruction, &mnemonics).map_err(error)?;
+        parsed.push((n, line, mnemonic, operands));
+    }
+
+    if label_indices.values().any(|i| *i == parsed.len()) {
+        return Err("Labels must be followed by an instruction".to_string());
+    }
+
+    // Every instruction is given an id as its ip, which branches to its label use as their target.
+    // The block encoder then replaces the ids with the real addresses. The ids must not be the
+    // same as any number in the code, or a branch to that address would be moved too.
+    let numbers: HashSet<u64> = parsed
+        .iter()
+        .flat_map(|(_, _, _, operands)| operands)
+        .filter_map(|o| match o {
+            Operand::Immediate(v) => u64::try_from(*v).ok(),
+            _ => None,
+        })
+        .collect();
+
+    let ids: Vec<u64> = (1..)
+        .filter(|id| !numbers.contains(id))
+        .take(parsed.len())
+        .collect();
+
+    let labels: HashMap<String, u64> = label_indices
+        .into_iter()
+        .map(|(name, i)| (name, ids[i]))
+        .collect();
+
+    let mut instructions = Vec::new();
+
+    for ((n, line, mnemonic, operands), id) in parsed.into_iter().zip(&ids) {
+        let mut instruction = build_instruction(mnemonic, &operands, &labels, address)
+            .map_err(|e| format!("Line {} \"{}\": {}", n + 1, line, e))?;
+
+        instruction.set_ip(*id);
+        instructions.push(instruction);
+    }
+
+    let block = InstructionBlock::new(&instructions, address.into());
+
+    BlockEncoder::encode(32, block, BlockEncoderOptions::NONE)
+        .map(|result| result.code_buffer)
+        .map_err(|e| e.to_string())
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_instruction(
+    text: &str,
+    mnemonics: &HashMap<String, Mnemonic>,
+) -> Result<(Mnemonic, Vec<Operand>), String> {
+    let (name, rest) = match text.split_once(char::is_whitespace) {
+        Some((name, rest)) => (name, rest.trim()),
+        None => (text, ""),
+    };
+
+    let name = name.to_lowercase();
+    let name = JCC_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map(|(_, actual)| actual.to_string())
+        .unwrap_or(name);
+
+    let mnemonic = *mnemonics
+        .get(&name)
+        .ok_or(format!("Unknown instruction \"{}\"", name))?;
+
+    let operands = if rest.is_empty() {
+        Vec::new()
+    } else {
+        rest.split(',')
+            .map(|o| parse_operand(o.trim()))
+            .collect::<Result<Vec<Operand>, String>>()?
+    };
+
+    Ok((mnemonic, operands))
+}
+
+fn parse_register(text: &str) -> Option<Register> {
+    // x87 registers can be written as either st(1) or st1
+    let text = text.to_lowercase().replace(['(', ')'], "");
+
+    Register::values().find(|r| {
+        (r.is_gpr8() || r.is_gpr16() || r.is_gpr32() || r.is_segment_register())
+            || r.is_xmm() && (*r as u32) <= Register::XMM7 as u32
+            || r.is_mm()
+            || r.is_st()
+    } && format!("{:?}", r).to_lowercase() == text)
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest.trim()),
+        None => (false, text),
+    };
+
+    let value = if let Some(hex) = text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(hex) = text.strip_suffix('h').or(text.strip_suffix('H')) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else {
+        text.parse::<i64>().ok()?
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    if text.is_empty() {
+        return Err("Missing operand".to_string());
+    }
+
+    if let Some(register) = parse_register(text) {
+        return Ok(Operand::Register(register));
+    }
+
+    if let Some(value) = parse_number(text) {
+        return Ok(Operand::Immediate(value));
+    }
+
+    if text.contains('[') {
+        return parse_memory(text).map(Operand::Memory);
+    }
+
+    if is_identifier(text) {
+        return Ok(Operand::Label(text.to_lowercase()));
+    }
+
+    Err(format!("Unable to understand the operand \"{}\"", text))
+}
+
+fn parse_memory(text: &str) -> Result<Memory, String> {
+    let (prefix, rest) = text.split_once('[').unwrap_or_default();
+    let inner = rest
+        .strip_suffix(']')
+        .ok_or(format!("Expected \"]\" at the end of \"{}\"", text))?;
+
+    let mut memory = Memory {
+        size: None,
+        segment: Register::None,
+        base: Register::None,
+        index: Register::None,
+        scale: 1,
+        displacement: 0,
+    };
+
+    // eg. "dword ptr fs:"
+    for word in prefix.to_lowercase().split_whitespace() {
+        let word = word.trim_end_matches(':');
+
+        memory.size = match word {
+            "byte" => Some(1),
+            "word" => Some(2),
+            "dword" => Some(4),
+            "qword" => Some(8),
+            "tbyte" | "tword" => Some(10),
+            "xmmword" | "oword" => Some(16),
+            "ptr" => continue,
+            _ => match parse_register(word) {
+                Some(r) if r.is_segment_register() => {
+                    memory.segment = r;
+                    continue;
+                }
+                _ => return Err(format!("Unable to understand \"{}\"", word)),
+            },
+        };
+    }
+
+    let mut term = String::new();
+    let mut terms = Vec::new();
+
+    for c in inner.chars().filter(|c| !c.is_whitespace()) {
+        if (c == '+' || c == '-') && !term.is_empty() {
+            terms.push(std::mem::take(&mut term));
+        }
+        term.push(c);
+    }
+    terms.push(term);
+
+    for term in terms {
+        let (negative, term) = match term.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, term.trim_start_matches('+')),
+        };
+
+        if let Some(value) = parse_number(term) {
+            memory.displacement += if negative { -value } else { value };
+            continue;
+        }
+
+        if negative {
+            return Err(format!("Registers can't be subtracted in \"{}\"", text));
+        }
+
+        let (register, scale) = match term.split_once('*') {
+            Some((a, b)) => match (parse_register(a), parse_number(b)) {
+                (Some(r), Some(s)) => (r, Some(s)),
+                _ => match (parse_number(a), parse_register(b)) {
+                    (Some(s), Some(r)) => (r, Some(s)),
+                    _ => return Err(format!("Unable to understand \"{}\"", term)),
+                },
+            },
+            None => match parse_register(term) {
+                Some(r) => (r, None),
+                None => return Err(format!("Unable to understand \"{}\"", term)),
+            },
+        };
+
+        if !register.is_gpr32() {
+            return Err(format!("{:?} can't be used to address memory", register));
+        }
+
+        match scale {
+            Some(s @ (1 | 2 | 4 | 8)) if memory.index == Register::None => {
+                memory.index = register;
+                memory.scale = s as u32;
+            }
+            None if memory.base == Register::None => memory.base = register,
+            None if memory.index == Register::None => memory.index = register,
+            _ => return Err(format!("Unable to understand \"{}\"", text)),
+        }
+    }
+
+    Ok(memory)
+}
+
+fn register_fits(kind: OpCodeOperandKind, register: Register) -> bool {
+    use OpCodeOperandKind as K;
+
+    match kind {
+        K::r8_reg | K::r8_opcode | K::r8_or_mem => register.is_gpr8(),
+        K::r16_reg | K::r16_opcode | K::r16_or_mem | K::r16_rm | K::r16_reg_mem => {
+            register.is_gpr16()
+        }
+        K::r32_reg | K::r32_opcode | K::r32_or_mem | K::r32_rm | K::r32_reg_mem => {
+            register.is_gpr32()
+        }
+        K::xmm_reg | K::xmm_rm | K::xmm_or_mem => register.is_xmm(),
+        K::mm_reg | K::mm_rm | K::mm_or_mem => register.is_mm(),
+        K::seg_reg => register.is_segment_register(),
+        K::sti_opcode => register.is_st(),
+        K::st0 => register == Register::ST0,
+        K::al => register == Register::AL,
+        K::cl => register == Register::CL,
+        K::ax => register == Register::AX,
+        K::dx => register == Register::DX,
+        K::eax => register == Register::EAX,
+        _ => false,
+    }
+}
+
+fn memory_fits(kind: OpCodeOperandKind) -> bool {
+    use OpCodeOperandKind as K;
+
+    matches!(
+        kind,
+        K::mem | K::r8_or_mem | K::r16_or_mem | K::r32_or_mem | K::xmm_or_mem | K::mm_or_mem
+    )
+}
+
+fn immediate_op_kind(kind: OpCodeOperandKind, value: i64) -> Option<OpKind> {
+    use OpCodeOperandKind as K;
+
+    match kind {
+        K::imm8 if (-128..=255).contains(&value) => Some(OpKind::Immediate8),
+        K::imm8_const_1 if value == 1 => Some(OpKind::Immediate8),
+        K::imm8sex16 if (-128..=127).contains(&value) => Some(OpKind::Immediate8to16),
+        K::imm8sex32 if (-128..=127).contains(&value) => Some(OpKind::Immediate8to32),
+        K::imm16 if (-32768..=65535).contains(&value) => Some(OpKind::Immediate16),
+        K::imm32 if (i32::MIN as i64..=u32::MAX as i64).contains(&value) => {
+            Some(OpKind::Immediate32)
+        }
+        _ => None,
+    }
+}
+
+/// Tries to fill in an instruction with the given operands, returning None if they don't fit the
+/// code's operands.
+fn try_code(
+    code: Code,
+    operands: &[Operand],
+    labels: &HashMap<String, u64>,
+) -> Result<Option<Instruction>, String> {
+    use OpCodeOperandKind as K;
+
+    let op_code = code.op_code();
+    let kinds = op_code.op_kinds();
+
+    if kinds.len() != operands.len() {
+        return Ok(None);
+    }
+
+    let mut instruction = Instruction::default();
+    instruction.set_code(code);
+
+    for (i, (kind, operand)) in kinds.iter().zip(operands).enumerate() {
+        let i = i as u32;
+
+        match operand {
+            Operand::Register(r) if register_fits(*kind, *r) => {
+                instruction.set_op_kind(i, OpKind::Register);
+                instruction.set_op_register(i, *r);
+            }
+            Operand::Memory(m) if memory_fits(*kind) => {
+                instruction.set_op_kind(i, OpKind::Memory);
+                instruction.set_segment_prefix(m.segment);
+                instruction.set_memory_base(m.base);
+                instruction.set_memory_index(m.index);
+                instruction.set_memory_index_scale(m.scale);
+                instruction.set_memory_displacement32(m.displacement as u32);
+
+                let displ_size = match (m.displacement, m.base, m.index) {
+                    (_, Register::None, Register::None) => 4,
+                    (0, _, _) => 0,
+                    _ => 1,
+                };
+                instruction.set_memory_displ_size(displ_size);
+            }
+            Operand::Immediate(v) if matches!(kind, K::br32_1 | K::br32_4) => {
+                let target = u32::try_from(*v).map_err(|_| format!("{:#x} isn't an address", v))?;
+
+                instruction.set_op_kind(i, OpKind::NearBranch32);
+                instruction.set_near_branch32(target);
+            }
+            Operand::Label(name) if matches!(kind, K::br32_1 | K::br32_4) => {
+                let id = labels
+                    .get(name)
+                    .ok_or(format!("The label \"{}\" isn't defined", name))?;
+
+                instruction.set_op_kind(i, OpKind::NearBranch32);
+                instruction.set_near_branch32(*id as u32);
+            }
+            Operand::Immediate(v) => match immediate_op_kind(*kind, *v) {
+                Some(op_kind) => {
+                    instruction.set_op_kind(i, op_kind);
+
+                    match op_kind {
+                        OpKind::Immediate8 => instruction.set_immediate8(*v as u8),
+                        OpKind::Immediate8to16 => instruction.set_immediate8to16(*v as i16),
+                        OpKind::Immediate8to32 => instruction.set_immediate8to32(*v as i32),
+                        OpKind::Immediate16 => instruction.set_immediate16(*v as u16),
+                        _ => instruction.set_immediate32(*v as u32),
+                    }
+                }
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        }
+    }
+
+    Ok(Some(instruction))
+}
+
+fn build_instruction(
+    mnemonic: Mnemonic,
+    operands: &[Operand],
+    labels: &HashMap<String, u64>,
+    address: u32,
+) -> Result<Instruction, String> {
+    // 16 bit forms are only used when an operand asks for them, so that eg. "push 5" isn't
+    // assembled as a 16 bit push
+    let uses_16_bit = operands.iter().any(|o| match o {
+        Operand::Register(r) => r.is_gpr16(),
+        Operand::Memory(m) => m.size == Some(2),
+        _ => false,
+    });
+
+    let explicit_size = operands.iter().find_map(|o| match o {
+        Operand::Memory(m) => m.size,
+        _ => None,
+    });
+
+    let mut candidates = Vec::new();
+
+    for code in Code::values().filter(|c| c.mnemonic() == mnemonic) {
+        let op_code = code.op_code();
+
+        if !op_code.is_instruction()
+            || !op_code.mode32()
+            || op_code.encoding() != EncodingKind::Legacy
+            || op_code.address_size() == 16
+            || (op_code.operand_size() == 16 && !uses_16_bit)
+        {
+            continue;
+        }
+
+        let Some(instruction) = try_code(code, operands, labels)? else {
+            continue;
+        };
+
+        if let Some(size) = explicit_size
+            && instruction.memory_size().size() != size
+        {
+            continue;
+        }
+
+        // Branches to labels are sized by the block encoder, so any target will do for now
+        let mut test = instruction;
+        if operands.iter().any(|o| matches!(o, Operand::Label(_))) {
+            test.set_near_branch32(address);
+        }
+
+        if let Ok(len) = Encoder::new(32).encode(&test, address.into()) {
+            candidates.push((len, instruction));
+        }
+    }
+
+    let memory_sizes: Vec<usize> = candidates
+        .iter()
+        .filter(|_| operands.iter().any(|o| matches!(o, Operand::Memory(_))))
+        .map(|(_, instruction)| instruction.memory_size().size())
+        .collect();
+
+    if explicit_size.is_none() && memory_sizes.iter().any(|s| *s != memory_sizes[0]) {
+        return Err("The operand size must be given, eg. \"dword ptr [eax]\"".to_string());
+    }
+
+    candidates
+        .into_iter()
+        .min_by_key(|(len, _)| *len)
+        .map(|(_, instruction)| instruction)
+        .ok_or("These operands can't be used with this instruction".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::assemble;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn assembles_simple_instructions() {
+        assert_eq!(
+            assemble(&lines(&["nop", "push eax", "mov eax, 5", "ret"]), 0x11000).unwrap(),
+            vec![0x90, 0x50, 0xb8, 0x05, 0x00, 0x00, 0x00, 0xc3]
+        );
+
+        assert_eq!(
+            assemble(&lines(&["mov dword ptr [esp + 8], 0x3f800000"]), 0x11000).unwrap(),
+            vec![0xc7, 0x44, 0x24, 0x08, 0x00, 0x00, 0x80, 0x3f]
+        );
+
+        assert_eq!(
+            assemble(&lines(&["add esp, 4"]), 0x11000).unwrap(),
+            vec![0x83, 0xc4, 0x04]
+        );
+    }
+
+    #[test]
+    fn branches_are_relative_to_the_address() {
+        // The call in "Randomise replay scene" from ghoulies.json
+        assert_eq!(
+            assemble(&lines(&["call 0x37907d"]), 0xe4e59).unwrap(),
+            vec![0xe8, 0x1f, 0x42, 0x29, 0x00]
+        );
+
+        assert_eq!(
+            assemble(&lines(&["jmp 0x11010"]), 0x11000).unwrap(),
+            vec![0xeb, 0x0e]
+        );
+    }
+
+    #[test]
+    fn labels_can_be_jumped_to() {
+        assert_eq!(
+            assemble(
+                &lines(&[
+                    "test eax, eax",
+                    "jz done ; skip the increment",
+                    "inc eax",
+                    "done: ret"
+                ]),
+                0x11000
+            )
+            .unwrap(),
+            vec![0x85, 0xc0, 0x74, 0x01, 0x40, 0xc3]
+        );
+
+        // Branches to numbers go to those addresses, even when there are labels
+        assert_eq!(
+            assemble(&lines(&["jmp 0x1", "jmp 0x2", "a: nop", "b: ret"]), 0x11000).unwrap(),
+            vec![
+                0xe9, 0xfc, 0xef, 0xfe, 0xff, 0xe9, 0xf8, 0xef, 0xfe, 0xff, 0x90, 0xc3
+            ]
+        );
+    }
+
+    #[test]
+    fn explains_mistakes() {
+        assert!(assemble(&lines(&["mov [eax], 5"]), 0x11000).is_err());
+        assert!(assemble(&lines(&["jmp nowhere"]), 0x11000).is_err());
+        assert!(assemble(&lines(&["nop", "done:"]), 0x11000).is_err());
+        assert!(assemble(&lines(&["a: nop", "a: nop"]), 0x11000).is_err());
+        assert!(assemble(&lines(&["frobnicate eax"]), 0x11000).is_err());
+    }
+}
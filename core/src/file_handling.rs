@@ -0,0 +1,118 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Seek, SeekFrom},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+pub struct LiveFile<T>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+{
+    file: File,
+    path: PathBuf,
+    backup_path: PathBuf,
+    data: T,
+}
+
+impl<T> LiveFile<T>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+{
+    pub fn from_new(path: &PathBuf, data: T) -> Result<Self, std::io::Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(path)?;
+
+        let mut backup_path = path.clone();
+        backup_path.set_extension("json.bak");
+
+        Ok(LiveFile {
+            file,
+            path: path.clone(),
+            backup_path,
+            data,
+        })
+    }
+
+    pub fn from_existing(path: &PathBuf) -> Result<Self, std::io::Error> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        let data: T = serde_json::from_reader(&file)?;
+
+        let mut backup_path = path.clone();
+        backup_path.set_extension("json.bak");
+
+        Ok(LiveFile {
+            file,
+            path: path.clone(),
+            backup_path,
+            data,
+        })
+    }
+
+    pub fn save(&mut self) -> Result<(), std::io::Error> {
+        // Backed up before the file is cleared, so a failed write leaves the backup behind
+        std::fs::copy(&self.path, &self.backup_path)?;
+
+        // Clear the file
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.set_len(0)?;
+
+        serde_json::to_writer_pretty(&self.file, &self.data)?;
+        self.file.sync_all()?;
+        std::fs::remove_file(&self.backup_path)?;
+
+        Ok(())
+    }
+
+    pub fn data(&self) -> &T {
+        &self.data
+    }
+
+    pub fn update<F>(&mut self, f: F) -> Result<(), std::io::Error>
+    where
+        F: FnOnce(&mut T),
+    {
+        f(&mut self.data);
+        self.save()?;
+
+        Ok(())
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{file_handling::LiveFile, test_util::temp_folder};
+
+    #[test]
+    fn saving_keeps_a_backup_until_the_file_is_written() {
+        let folder = temp_folder("saving_keeps_a_backup_until_the_file_is_written");
+        let path = folder.join("ghoulies.json");
+        std::fs::write(&path, "[1, 2]").unwrap();
+
+        let mut file = LiveFile::<Vec<u32>>::from_existing(&path).unwrap();
+
+        // The backup is taken from the file as it was before saving
+        std::fs::create_dir(folder.join("ghoulies.json.bak")).unwrap();
+        assert!(file.update(|data| data.push(3)).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[1, 2]");
+
+        std::fs::remove_dir(folder.join("ghoulies.json.bak")).unwrap();
+        file.update(|data| data.push(4)).unwrap();
+
+        let saved: Vec<u32> = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved, vec![1, 2, 3, 4]);
+        assert!(!folder.join("ghoulies.json.bak").exists());
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+}
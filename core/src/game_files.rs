@@ -0,0 +1,582 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    HasPatches,
+    patching::{
+        Patch, PatchContents, PatchEntry, PatchOffsetType, ResolvedPatch,
+        file_change::{FileAction, FileChange, MAX_FILE_SIZE},
+        operation,
+        overlap::{self, PatchOverlap},
+        text,
+    },
+    xbe::{PatchReport, XBEReader, XBEWriter},
+};
+
+/// Where the files changed by earlier patching runs are listed, in the backup folder.
+pub const CHANGED_FILES_NAME: &str = "xbpatch_changed_files.json";
+
+/// A file other than default.xbe that is being patched.
+#[derive(Debug)]
+enum GameFile {
+    Xbe(XBEWriter),
+    Data(File),
+
+    // A file that an entry replaces, adds or deletes, which is only opened once it has been
+    // changed. Until then its patches are worked out from the file it will be a copy of.
+    Pending(Option<PathBuf>),
+}
+
+/// The files that patching has changed, so that they can be put back before patching again even
+/// if the entries that changed them are no longer enabled.
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct ChangedFiles {
+    // Files with a backup of how they were before they were first patched
+    #[serde(default)]
+    backed_up: Vec<String>,
+
+    // Files that weren't in the game, and are deleted again
+    #[serde(default)]
+    added: Vec<String>,
+}
+
+/// Every file in an extracted game that a set of entries writes to. Patches write to default.xbe
+/// unless they name another file, which is found relative to the folder default.xbe is in.
+///
+/// Backups of the files, and the list of what has been changed, are kept in a backup folder
+/// outside the game, so that they aren't packed into the rebuilt ISO.
+#[derive(Debug)]
+pub struct GameFiles {
+    root: PathBuf,
+    backups: PathBuf,
+    xbe_path: PathBuf,
+    xbe: XBEWriter,
+    others: HashMap<String, GameFile>,
+
+    // Changes to whole files, made by apply_file_changes once the user has agreed to them
+    changes: Vec<FileChange>,
+    changed: ChangedFiles,
+
+    // Files are only restored once, so that later changes don't undo earlier ones
+    prepared: HashSet<PathBuf>,
+}
+
+/// Whether a patch's file stays inside the game's folder, so a patch set can't write anywhere else
+/// on the user's computer.
+pub fn is_game_file_path(file: &str) -> bool {
+    let path = Path::new(file);
+
+    !file.is_empty() && path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+impl GameFiles {
+    /// Opens every file that the entries patch alongside default.xbe. Files changed by an earlier
+    /// run are put back first: each is restored from its backup in `backups`, or deleted if it was
+    /// added, and files patched for the first time are backed up there.
+    ///
+    /// Nothing else in the game is changed until apply_file_changes is called, so the entries can
+    /// be checked for overlaps before the user decides whether to go ahead.
+    pub fn open(
+        xbe_path: &Path,
+        xbe: XBEWriter,
+        entries: &[PatchEntry],
+        backups: &Path,
+    ) -> Result<GameFiles, std::io::Error> {
+        let root = xbe_path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        let changed = match std::fs::read(backups.join(CHANGED_FILES_NAME)) {
+            Ok(contents) => serde_json::from_slice(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => ChangedFiles::default(),
+            Err(e) => return Err(e),
+        };
+
+        let mut game_files = GameFiles {
+            root,
+            backups: backups.to_path_buf(),
+            xbe_path: xbe_path.to_path_buf(),
+            xbe,
+            others: HashMap::new(),
+            changes: entries.iter().flat_map(|e| e.files()).cloned().collect(),
+            changed,
+            prepared: HashSet::new(),
+        };
+
+        game_files.restore_changed_files()?;
+
+        // Every change is checked before any are made, so a bad one doesn't leave the game half
+        // changed
+        for change in &game_files.changes {
+            game_files.check_file_change(change)?;
+        }
+
+        for patch in entries.iter().flat_map(|e| e.get_patches()) {
+            let Some(file) = &patch.file else {
+                continue;
+            };
+
+            if game_files.others.contains_key(file)
+                || game_files.path_of(file)? == game_files.xbe_path
+            {
+                continue;
+            }
+
+            // The last change to a file decides what it will be once the changes are made
+            let pending = game_files
+                .changes
+                .iter()
+                .rev()
+                .find(|c| c.path == *file)
+                .map(|c| GameFile::Pending(c.source_path.clone()));
+
+            let game_file = match pending {
+                Some(pending) => pending,
+                None => game_files.open_file(file)?,
+            };
+
+            game_files.others.insert(file.clone(), game_file);
+        }
+
+        Ok(game_files)
+    }
+
+    /// Replaces, adds and deletes the entries' whole files, then opens any of them that are
+    /// patched.
+    pub fn apply_file_changes(&mut self) -> Result<(), std::io::Error> {
+        for change in std::mem::take(&mut self.changes) {
+            self.change_file(&change)?;
+        }
+
+        let pending: Vec<String> = self
+            .others
+            .iter()
+            .filter(|(_, f)| matches!(f, GameFile::Pending(_)))
+            .map(|(file, _)| file.clone())
+            .collect();
+
+        for file in pending {
+            let game_file = self.open_file(&file)?;
+            self.others.insert(file, game_file);
+        }
+
+        Ok(())
+    }
+
+    fn open_file(&mut self, file: &str) -> Result<GameFile, std::io::Error> {
+        let path = self.path_of(file)?;
+
+        // Added files are deleted rather than restored, so they don't need a backup
+        if !self.changed.added.iter().any(|a| a == file) {
+            self.prepare(file)?;
+        }
+
+        Ok(if is_xbe_path(&path) {
+            GameFile::Xbe(XBEWriter::new(&path)?)
+        } else {
+            GameFile::Data(OpenOptions::new().read(true).write(true).open(&path)?)
+        })
+    }
+
+    // Puts back every file changed by an earlier run, whether or not this run changes it again
+    fn restore_changed_files(&mut self) -> Result<(), std::io::Error> {
+        for file in std::mem::take(&mut self.changed.added) {
+            let path = self.path_of(&file)?;
+
+            if path.is_file() {
+                std::fs::remove_file(&path)?;
+            }
+        }
+
+        for file in self.changed.backed_up.clone() {
+            let path = self.path_of(&file)?;
+            let backup = self.backups.join(&file);
+
+            if self.prepared.insert(path.clone()) && backup.is_file() {
+                std::fs::copy(&backup, &path)?;
+            }
+        }
+
+        self.save_changed_files()
+    }
+
+    // Nothing is written until a file has been changed, and the list is removed once every file
+    // has been put back
+    fn save_changed_files(&self) -> Result<(), std::io::Error> {
+        let path = self.backups.join(CHANGED_FILES_NAME);
+
+        if self.changed.backed_up.is_empty() && self.changed.added.is_empty() {
+            return match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+
+        std::fs::create_dir_all(&self.backups)?;
+        std::fs::write(path, serde_json::to_vec_pretty(&self.changed)?)
+    }
+
+    // Restores a file from its backup, or backs it up if it hasn't been patched before
+    fn prepare(&mut self, file: &str) -> Result<(), std::io::Error> {
+        let path = self.path_of(file)?;
+        let backup = self.backups.join(file);
+
+        if !self.prepared.insert(path.clone()) {
+            return Ok(());
+        }
+
+        if backup.is_file() {
+            std::fs::copy(&backup, &path)?;
+        } else if path.exists() {
+            if let Some(parent) = backup.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            std::fs::copy(&path, &backup)?;
+        }
+
+        if !self.changed.backed_up.iter().any(|b| b == file) {
+            self.changed.backed_up.push(file.to_string());
+            self.save_changed_files()?;
+        }
+
+        Ok(())
+    }
+
+    fn check_file_change(&self, change: &FileChange) -> Result<(), std::io::Error> {
+        change.check().map_err(|(field, reason)| {
+            std::io::Error::other(format!("Invalid {} of {}: {}.", field, change.path, reason))
+        })?;
+
+        if self.path_of(&change.path)? == self.xbe_path {
+            return Err(std::io::Error::other(
+                "default.xbe can't be replaced, added or deleted. Use patches to change it instead.",
+            ));
+        }
+
+        let Some(source) = &change.source_path else {
+            return Ok(());
+        };
+
+        let metadata = std::fs::metadata(source).map_err(|e| {
+            std::io::Error::other(format!(
+                "Unable to read {} to copy into the game. Error: {}",
+                source.display(),
+                e
+            ))
+        })?;
+
+        if !metadata.is_file() {
+            return Err(std::io::Error::other(format!(
+                "{} is not a file.",
+                source.display()
+            )));
+        }
+
+        if metadata.len() > MAX_FILE_SIZE {
+            return Err(std::io::Error::other(format!(
+                "{} is too large to be put on an Xbox disc.",
+                source.display()
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn change_file(&mut self, change: &FileChange) -> Result<(), std::io::Error> {
+        let path = self.path_of(&change.path)?;
+
+        if change.action != FileAction::Add {
+            self.prepare(&change.path)?;
+
+            if !path.is_file() {
+                return Err(std::io::Error::other(format!(
+                    "{} isn't in the game.",
+                    change.path
+                )));
+            }
+        }
+
+        let Some(source) = &change.source_path else {
+            return std::fs::remove_file(&path);
+        };
+
+        if change.action == FileAction::Add {
+            // Files recorded as added by earlier runs have already been deleted, but ones added
+            // before they were recorded are still there
+            if path.exists()
+                && !self.changed.added.contains(&change.path)
+                && std::fs::read(&path)? != std::fs::read(source)?
+            {
+                return Err(std::io::Error::other(format!(
+                    "{} is already in the game. Replace it instead.",
+                    change.path
+                )));
+            }
+
+            // Recorded before the file is copied, so it is deleted even if copying fails partway
+            if !self.changed.added.contains(&change.path) {
+                self.changed.added.push(change.path.clone());
+                self.save_changed_files()?;
+            }
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::copy(source, &path)?;
+        Ok(())
+    }
+
+    fn path_of(&self, file: &str) -> Result<PathBuf, std::io::Error> {
+        if !is_game_file_path(file) {
+            return Err(std::io::Error::other(format!(
+                "\"{}\" is not a path inside the game's folder.",
+                file
+            )));
+        }
+
+        Ok(self.root.join(file))
+    }
+
+    /// Works out exactly which bytes a patch will write, and to which file.
+    pub fn resolve_patch(&self, patch: &Patch) -> Result<ResolvedPatch, std::io::Error> {
+        let Some(file) = &patch.file else {
+            return self.xbe.resolve_patch(patch);
+        };
+
+        let resolved = match self.others.get(file) {
+            // default.xbe can be named like any other file
+            None if self.path_of(file)? == self.xbe_path => return self.xbe.resolve_patch(patch),
+            None => {
+                return Err(std::io::Error::other(format!(
+                    "{} has not been opened for patching.",
+                    file
+                )));
+            }
+            Some(GameFile::Xbe(writer)) => writer.resolve_patch(patch)?,
+            Some(GameFile::Data(data)) => resolve_data_patch(data, file, patch)?,
+            Some(GameFile::Pending(None)) => {
+                return Err(std::io::Error::other(format!(
+                    "{} is deleted by an entry, so it can't be patched.",
+                    file
+                )));
+            }
+            Some(GameFile::Pending(Some(source))) if is_xbe_path(source) => {
+                XBEReader::new(source)?.resolve_patch(patch)?
+            }
+            Some(GameFile::Pending(Some(source))) => {
+                resolve_data_patch(&File::open(source)?, file, patch)?
+            }
+        };
+
+        Ok(ResolvedPatch {
+            file: Some(file.clone()),
+            ..resolved
+        })
+    }
+
+    /// Finds entries that would write over each other's bytes if they were all applied.
+    pub fn find_overlaps(
+        &self,
+        entries: &[PatchEntry],
+    ) -> Result<Vec<PatchOverlap>, std::io::Error> {
+        overlap::find_overlaps(entries, |p| self.resolve_patch(p))
+    }
+
+    pub fn apply_patch(&mut self, patch: &Patch) -> Result<(), std::io::Error> {
+        let resolved = self.resolve_patch(patch)?;
+
+        let writer = match patch.file.as_ref().and_then(|f| self.others.get_mut(f)) {
+            None => return self.xbe.apply_patch(patch),
+            Some(GameFile::Xbe(writer)) => return writer.apply_patch(patch),
+            Some(GameFile::Data(data)) => data,
+            Some(GameFile::Pending(_)) => {
+                return Err(std::io::Error::other(format!(
+                    "The entries' files must be changed before {} can be patched.",
+                    resolved.file.unwrap_or_default()
+                )));
+            }
+        };
+
+        writer.seek(SeekFrom::Start(resolved.file_offset))?;
+        writer.write_all(&resolved.bytes)?;
+        Ok(())
+    }
+
+    pub fn apply_patches<T: HasPatches>(
+        &mut self,
+        entry: &T,
+    ) -> Result<PatchReport, std::io::Error> {
+        let mut report = PatchReport::default();
+
+        for patch in entry.get_patches() {
+            report.increment_from_bool(self.apply_patch(patch).is_ok());
+        }
+
+        Ok(report)
+    }
+}
+
+fn is_xbe_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("xbe"))
+}
+
+// Files that aren't XBEs have no memory map, so only raw offsets can be used
+fn resolve_data_patch(
+    data: &File,
+    file: &str,
+    patch: &Patch,
+) -> Result<ResolvedPatch, std::io::Error> {
+    if !matches!(patch.offset_type, PatchOffsetType::Raw) {
+        return Err(std::io::Error::other(format!(
+            "Only Raw offsets can be used to patch {}.",
+            file
+        )));
+    }
+
+    let bytes = match &patch.contents {
+        PatchContents::Text {
+            text,
+            encoding,
+            padding,
+            max_length,
+        } => text::text_patch_bytes(patch, text, *encoding, *padding, *max_length)?,
+        PatchContents::Fill { fill, length } => operation::fill(fill, *length).map_err(|e| {
+            std::io::Error::other(format!("Unable to fill at 0x{:X}: {}.", patch.offset, e))
+        })?,
+        contents => contents.fixed_bytes().ok_or(std::io::Error::other(format!(
+            "Only patches that don't depend on the XBE can be written to {}.",
+            file
+        )))?,
+    };
+
+    let end = u64::from(patch.offset) + bytes.len() as u64;
+
+    if end > data.metadata()?.len() {
+        return Err(std::io::Error::other(format!(
+            "The patch at 0x{:X} goes past the end of {}.",
+            patch.offset, file
+        )));
+    }
+
+    Ok(ResolvedPatch {
+        file: Some(file.to_string()),
+        file_offset: patch.offset.into(),
+        bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{
+        game_files::{CHANGED_FILES_NAME, GameFiles, is_game_file_path},
+        patching::PatchEntry,
+        test_util::{temp_folder, test_xbe},
+        xbe::XBEWriter,
+    };
+
+    #[test]
+    fn files_must_stay_in_the_game_folder() {
+        assert!(is_game_file_path("media/config.ini"));
+        assert!(is_game_file_path("levels/level01.xbe"));
+
+        assert!(!is_game_file_path(""));
+        assert!(!is_game_file_path("../default.xbe"));
+        assert!(!is_game_file_path("media/../../secrets.txt"));
+        assert!(!is_game_file_path("/etc/passwd"));
+        assert!(!is_game_file_path("./default.xbe"));
+    }
+
+    #[test]
+    fn files_are_changed_after_opening_and_put_back_next_time() {
+        let folder = temp_folder("game_files");
+        let game = folder.join("game");
+        std::fs::create_dir_all(game.join("media")).unwrap();
+
+        let xbe_path = game.join("default.xbe");
+        std::fs::write(&xbe_path, test_xbe(&[(".text", 0x11000, &[0x90; 0x20])])).unwrap();
+        std::fs::write(game.join("media/config.ini"), "fps=30").unwrap();
+        std::fs::write(folder.join("extra.ini"), "bonus=1").unwrap();
+
+        let mut entry: PatchEntry = serde_json::from_value(serde_json::json!({
+            "name": "Extra config",
+            "description": "",
+            "alt_author": null,
+            "patches": [
+                { "offset": "0x4", "offset_type": "Raw", "file": "media/extra.ini", "replacement_bytes": "0x32" }
+            ],
+            "files": [
+                { "path": "media/extra.ini", "action": "Add", "source": "extra.ini" },
+                { "path": "media/config.ini", "action": "Delete" }
+            ]
+        }))
+        .unwrap();
+
+        for change in entry.files_mut() {
+            change.resolve_source(Some(&folder));
+        }
+
+        let backups = folder.join("backups");
+        let entries = vec![entry];
+        let open = || {
+            GameFiles::open(
+                &xbe_path,
+                XBEWriter::new(&xbe_path).unwrap(),
+                &entries,
+                &backups,
+            )
+            .unwrap()
+        };
+
+        // Nothing is written until a file is changed
+        drop(open());
+        assert!(!backups.exists());
+
+        // Overlaps are found from the file being added, without touching the game
+        let mut game_files = open();
+        assert!(game_files.find_overlaps(&entries).unwrap().is_empty());
+        assert!(!game.join("media/extra.ini").exists());
+        assert!(game.join("media/config.ini").exists());
+
+        game_files.apply_file_changes().unwrap();
+        game_files.apply_patches(&entries[0]).unwrap();
+        assert!(backups.join(CHANGED_FILES_NAME).is_file());
+        assert!(backups.join("media/config.ini").is_file());
+        assert_eq!(
+            std::fs::read(game.join("media/extra.ini")).unwrap(),
+            b"bonu2=1"
+        );
+        assert!(!game.join("media/config.ini").exists());
+
+        // Opening again puts the game back as it was, whether or not the entry is still enabled
+        drop(game_files);
+        GameFiles::open(&xbe_path, XBEWriter::new(&xbe_path).unwrap(), &[], &backups).unwrap();
+
+        assert!(!game.join("media/extra.ini").exists());
+        assert_eq!(
+            std::fs::read(game.join("media/config.ini")).unwrap(),
+            b"fps=30"
+        );
+
+        // Nothing is left in the game that would be packed into the ISO
+        let names = |folder: PathBuf| {
+            let mut names: Vec<String> = std::fs::read_dir(folder)
+                .unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        let in_game = [names(game.clone()), names(game.join("media"))].concat();
+        assert_eq!(in_game, vec!["default.xbe", "media", "config.ini"]);
+    }
+}
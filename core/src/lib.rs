@@ -1,6 +1,12 @@
+pub mod assembler;
+pub mod file_handling;
+pub mod game_files;
 pub mod iso_handling;
 pub mod memory;
 pub mod patching;
 pub use patching::HasPatches;
 
 pub mod xbe;
+
+#[cfg(test)]
+mod test_util;
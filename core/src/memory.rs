@@ -10,6 +10,9 @@ pub struct MemoryMapping {
     pub file_start: u32,
     pub virtual_start: u32,
     pub size: u32,
+
+    // How much of the section is in the file, which can be less than its size in memory
+    pub file_size: u32,
 }
 
 impl MemoryMap {
@@ -21,6 +24,7 @@ impl MemoryMap {
                 file_start: section.file_offset,
                 virtual_start: section.virtual_offset,
                 size: section.virtual_size,
+                file_size: section.file_size,
             });
         }
 
@@ -39,6 +43,27 @@ impl MemoryMap {
         ))
     }
 
+    pub fn get_virtual_address(&self, raw_offset: u32) -> Result<u32, std::io::Error> {
+        for mapping in &self.mappings {
+            let Some(file_end) = mapping.file_start.checked_add(mapping.file_size) else {
+                continue;
+            };
+
+            if mapping.file_start <= raw_offset && raw_offset < file_end {
+                return mapping
+                    .virtual_start
+                    .checked_add(raw_offset - mapping.file_start)
+                    .ok_or(std::io::Error::other(
+                        "The raw offset maps past the end of memory.",
+                    ));
+            }
+        }
+
+        Err(std::io::Error::other(
+            "The memory map does not contain the raw offset specified.",
+        ))
+    }
+
     pub fn new(mappings: Vec<MemoryMapping>) -> MemoryMap {
         MemoryMap { mappings }
     }
@@ -61,11 +86,13 @@ mod tests {
                     file_start: 0x0,
                     virtual_start: 0x00010000,
                     size: 0xf60,
+                    file_size: 0xf60,
                 },
                 MemoryMapping {
                     file_start: 0x1000,
                     virtual_start: 0x00011000,
                     size: 0x160020,
+                    file_size: 0x160020,
                 },
             ],
         }
@@ -87,6 +114,23 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn offsets_past_the_end_of_a_section_arent_mapped() {
+        let mem = get_test_mem_map();
+
+        assert_eq!(mem.get_virtual_address(0xf5f).unwrap(), 0x10f5f);
+        assert!(mem.get_virtual_address(0xf60).is_err());
+        assert!(mem.get_virtual_address(0x161020).is_err());
+
+        let overflowing = MemoryMap::new(vec![MemoryMapping {
+            file_start: 0xffff_f000,
+            virtual_start: 0x10000,
+            size: 0x2000,
+            file_size: 0x2000,
+        }]);
+        assert!(overflowing.get_virtual_address(0xffff_f800).is_err());
+    }
+
     /*
           {
       "offset": "0x11c7e1",
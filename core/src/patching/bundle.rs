@@ -0,0 +1,592 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+use crate::{
+    game_files::is_game_file_path,
+    patching::{
+        PatchSet,
+        signing::{self, Keyring, Trust},
+        validation::{self, ValidationProblem},
+    },
+};
+
+/// The extension given to bundle files.
+pub const BUNDLE_EXTENSION: &str = "xbpatch";
+
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+pub const CURRENT_BUNDLE_FORMAT: u32 = 1;
+
+// Far larger than any real manifest, so a bundle can't make the manifest use up all memory
+const MAX_MANIFEST_SIZE: u64 = 0x100000;
+
+/// Lists everything in a bundle along with its checksum, so that missing or altered files can be
+/// noticed before the bundle is used.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BundleManifest {
+    pub bundle_format: u32,
+
+    // The patch set's JSON file, which must also be listed in files
+    pub patch_set: String,
+
+    pub files: Vec<BundleFile>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BundleFile {
+    // Where the file is in the bundle, using forward slashes
+    pub path: String,
+
+    // SHA-1 of the file's contents, as 40 hex digits
+    pub sha1: String,
+
+    // The file's size in bytes. Nothing past this is read, so a small bundle can't unpack into
+    // a huge file.
+    pub size: u64,
+}
+
+/// A patch set packed into a single file along with the files it copies into the game, which is
+/// a zip archive with a manifest.
+pub struct PatchBundle {
+    archive: ZipArchive<File>,
+    manifest: BundleManifest,
+}
+
+impl PatchBundle {
+    pub fn open(path: &PathBuf) -> Result<PatchBundle, std::io::Error> {
+        let mut archive = ZipArchive::new(File::open(path)?)?;
+
+        let manifest = serde_json::from_slice(&read_file(
+            &mut archive,
+            MANIFEST_FILE_NAME,
+            MAX_MANIFEST_SIZE,
+        )?)
+        .map_err(|e| {
+            std::io::Error::other(format!("The bundle's manifest can't be read. Error: {}", e))
+        })?;
+
+        Ok(PatchBundle { archive, manifest })
+    }
+
+    pub fn manifest(&self) -> &BundleManifest {
+        &self.manifest
+    }
+
+    pub fn patch_set(&mut self) -> Result<PatchSet, std::io::Error> {
+        let contents = self.read_listed_file(&self.manifest.patch_set.clone())?;
+        Ok(serde_json::from_slice(&contents)?)
+    }
+
+    // Reads a file listed in the manifest, up to the size the manifest gives it
+    fn read_listed_file(&mut self, path: &str) -> Result<Vec<u8>, std::io::Error> {
+        let file =
+            self.manifest
+                .files
+                .iter()
+                .find(|f| f.path == path)
+                .ok_or(std::io::Error::other(format!(
+                    "{} isn't listed in the bundle's manifest.",
+                    path
+                )))?;
+
+        read_file(&mut self.archive, path, file.size)
+    }
+
+    /// Checks the bundle's files against its manifest, then checks its patch set. Problems in the
+    /// patch set have their paths prefixed with the patch set's file name.
+    pub fn validate(&mut self) -> Result<Vec<ValidationProblem>, std::io::Error> {
+        let mut problems = Vec::new();
+        let manifest = self.manifest.clone();
+
+        let in_manifest = |path: &str| format!("{}:{}", MANIFEST_FILE_NAME, path);
+
+        if manifest.bundle_format > CURRENT_BUNDLE_FORMAT {
+            problems.push(ValidationProblem::new(
+                in_manifest("bundle_format"),
+                format!(
+                    "the bundle is format {}, but only formats up to {} are supported",
+                    manifest.bundle_format, CURRENT_BUNDLE_FORMAT
+                ),
+            ));
+        }
+
+        let names: HashSet<String> = self
+            .archive
+            .file_names()
+            .map(|name| name.map(|n| n.to_string()))
+            .collect::<Result<_, _>>()?;
+
+        for (i, file) in manifest.files.iter().enumerate() {
+            let path = in_manifest(&format!("files[{}]", i));
+
+            if !is_game_file_path(&file.path) {
+                problems.push(ValidationProblem::new(
+                    format!("{}.path", path),
+                    format!("\"{}\" is not a path inside the bundle", file.path),
+                ));
+            } else if !names.contains(&file.path) {
+                problems.push(ValidationProblem::new(
+                    format!("{}.path", path),
+                    format!("{} is missing from the bundle", file.path),
+                ));
+            } else {
+                match read_file(&mut self.archive, &file.path, file.size) {
+                    Ok(contents) if !sha1_hex(&contents).eq_ignore_ascii_case(&file.sha1) => {
+                        problems.push(ValidationProblem::new(
+                            format!("{}.sha1", path),
+                            format!("{} doesn't match its checksum", file.path),
+                        ));
+                    }
+                    Ok(_) => (),
+                    Err(e) => {
+                        problems.push(ValidationProblem::new(
+                            format!("{}.size", path),
+                            e.to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+
+        for name in names.iter().filter(|n| !n.ends_with('/')) {
+            if name != MANIFEST_FILE_NAME && !manifest.files.iter().any(|f| &f.path == name) {
+                problems.push(ValidationProblem::new(
+                    name.clone(),
+                    "the file isn't listed in the manifest",
+                ));
+            }
+        }
+
+        if !manifest.files.iter().any(|f| f.path == manifest.patch_set) {
+            problems.push(ValidationProblem::new(
+                in_manifest("patch_set"),
+                format!(
+                    "{} isn't listed in the manifest's files",
+                    manifest.patch_set
+                ),
+            ));
+        }
+
+        // The patch set is only checked once the files it's read from can be trusted
+        if !problems.is_empty() {
+            return Ok(problems);
+        }
+
+        let contents = self.read_listed_file(&manifest.patch_set)?;
+        let in_patch_set = |path: &str| format!("{}:{}", manifest.patch_set, path);
+
+        let patch_set: serde_json::Value = match serde_json::from_slice(&contents) {
+            Ok(p) => p,
+            Err(e) => {
+                return Ok(vec![ValidationProblem::new(
+                    manifest.patch_set.clone(),
+                    e.to_string(),
+                )]);
+            }
+        };
+
+        let patch_set_problems = validation::validate(&patch_set);
+
+        if !patch_set_problems.is_empty() {
+            return Ok(patch_set_problems
+                .into_iter()
+                .map(|p| ValidationProblem::new(in_patch_set(&p.path), p.message))
+                .collect());
+        }
+
+        let patch_set: PatchSet = serde_json::from_value(patch_set)?;
+        let folder = bundle_folder_of(&manifest.patch_set);
+
+        for (i, entry) in patch_set.entries.iter().enumerate() {
+            for (j, change) in entry.files().iter().enumerate() {
+                let Some(source) = &change.source else {
+                    continue;
+                };
+
+                let source = format!("{}{}", folder, source.replace('\\', "/"));
+
+                if !manifest.files.iter().any(|f| f.path == source) {
+                    problems.push(ValidationProblem::new(
+                        in_patch_set(&format!("entries[{}].files[{}].source", i, j)),
+                        format!("{} isn't in the bundle", source),
+                    ));
+                }
+            }
+        }
+
+        Ok(problems)
+    }
+
+    /// Checks the signature of the bundle's patch set, which is bundled next to the patch set if
+    /// it was signed.
+    pub fn verify(&mut self, keyring: &Keyring) -> Result<Trust, std::io::Error> {
+        let patch_set = self.manifest.patch_set.clone();
+        let signature_name = format!("{}.{}", patch_set, signing::SIGNATURE_EXTENSION);
+
+        if !self.manifest.files.iter().any(|f| f.path == signature_name) {
+            return Ok(Trust::Unsigned);
+        }
+
+        let signature = match serde_json::from_slice(&self.read_listed_file(&signature_name)?) {
+            Ok(s) => s,
+            Err(e) => {
+                return Ok(Trust::Tampered(format!(
+                    "the signature can't be read ({})",
+                    e
+                )));
+            }
+        };
+
+        let folder = bundle_folder_of(&patch_set);
+
+        // Only files listed in the manifest count as part of the bundle
+        let manifest = self.manifest.clone();
+        let archive = &mut self.archive;
+
+        Ok(signing::verify_signature(
+            &signature,
+            &patch_set[folder.len()..],
+            |path| {
+                if !is_game_file_path(path) {
+                    return Err(std::io::Error::other(format!(
+                        "\"{}\" is outside the patch set",
+                        path
+                    )));
+                }
+
+                let path = format!("{}{}", folder, path);
+
+                let Some(file) = manifest.files.iter().find(|f| f.path == path) else {
+                    return Err(std::io::Error::other(format!(
+                        "{} isn't in the bundle",
+                        path
+                    )));
+                };
+
+                read_file(archive, &path, file.size)
+            },
+            keyring,
+        ))
+    }
+
+    /// Extracts the patch set and its files into a folder, keeping their layout so the patch set
+    /// can find its files. Nothing is extracted if the bundle has any problems, or if any of its
+    /// files are already in the folder. Returns the path of the extracted patch set.
+    pub fn extract(&mut self, folder: &Path) -> Result<PathBuf, std::io::Error> {
+        let problems = self.validate()?;
+
+        if !problems.is_empty() {
+            return Err(std::io::Error::other(format!(
+                "The bundle has {} problem(s):\n{}",
+                problems.len(),
+                problems
+                    .iter()
+                    .map(|p| format!("- {}", p))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            )));
+        }
+
+        if let Some(file) = self
+            .manifest
+            .files
+            .iter()
+            .find(|f| folder.join(&f.path).exists())
+        {
+            return Err(std::io::Error::other(format!(
+                "{} already exists, so the bundle won't be extracted over it.",
+                folder.join(&file.path).display()
+            )));
+        }
+
+        for file in &self.manifest.files {
+            let path = folder.join(&file.path);
+
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            std::fs::write(path, read_file(&mut self.archive, &file.path, file.size)?)?;
+        }
+
+        Ok(folder.join(&self.manifest.patch_set))
+    }
+}
+
+/// Packs a patch set and the source of every file it copies into the game into a bundle.
+pub fn create_bundle(
+    patch_set_path: &PathBuf,
+    bundle_path: &PathBuf,
+) -> Result<BundleManifest, std::io::Error> {
+    let patch_set = PatchSet::from_file(patch_set_path)?;
+    let folder = patch_set_path.parent().unwrap_or(Path::new(""));
+
+    // Included patch sets can be anywhere on the author's computer, so they can't be bundled yet
+    if !patch_set.includes.is_empty() {
+        return Err(std::io::Error::other(
+            "Patch sets that include other patch sets can't be bundled.",
+        ));
+    }
+
+    let patch_set_name = patch_set_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or(std::io::Error::other(
+            "The patch set's file name isn't valid.",
+        ))?
+        .to_string();
+
+    let mut paths = vec![patch_set_name.clone()];
+
+    // The signature travels with the patch set, so it can still be checked once extracted
+    if signing::signature_path(patch_set_path).is_file() {
+        paths.push(format!(
+            "{}.{}",
+            patch_set_name,
+            signing::SIGNATURE_EXTENSION
+        ));
+    }
+
+    for change in patch_set.entries.iter().flat_map(|e| e.files()) {
+        let Some(source) = &change.source else {
+            continue;
+        };
+
+        if !is_game_file_path(source) {
+            return Err(std::io::Error::other(format!(
+                "\"{}\" is not a path inside the patch set's folder.",
+                source
+            )));
+        }
+
+        let source = source.replace('\\', "/");
+
+        if !paths.contains(&source) {
+            paths.push(source);
+        }
+    }
+
+    let mut writer = ZipWriter::new(File::create(bundle_path)?);
+    let mut manifest = BundleManifest {
+        bundle_format: CURRENT_BUNDLE_FORMAT,
+        patch_set: patch_set_name,
+        files: Vec::new(),
+    };
+
+    for path in paths {
+        let contents = std::fs::read(folder.join(&path))
+            .map_err(|e| std::io::Error::other(format!("Unable to read {}. Error: {}", path, e)))?;
+
+        writer.start_file(path.as_str(), SimpleFileOptions::default())?;
+        writer.write_all(&contents)?;
+
+        manifest.files.push(BundleFile {
+            sha1: sha1_hex(&contents),
+            size: contents.len() as u64,
+            path,
+        });
+    }
+
+    writer.start_file(MANIFEST_FILE_NAME, SimpleFileOptions::default())?;
+    writer.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    writer.finish()?;
+
+    Ok(manifest)
+}
+
+// Reads at most one byte more than `size`, to tell whether the file is larger than it should be
+fn read_file(
+    archive: &mut ZipArchive<File>,
+    name: &str,
+    size: u64,
+) -> Result<Vec<u8>, std::io::Error> {
+    let file = archive
+        .by_name(name)
+        .map_err(|e| std::io::Error::other(format!("Unable to read {}. Error: {}", name, e)))?;
+
+    let mut contents = Vec::new();
+    file.take(size.saturating_add(1))
+        .read_to_end(&mut contents)?;
+
+    if contents.len() as u64 > size {
+        return Err(std::io::Error::other(format!(
+            "{} is larger than the {} bytes it should be.",
+            name, size
+        )));
+    }
+
+    Ok(contents)
+}
+
+// The folder a file is in inside the bundle, with a trailing slash unless it's the top level
+fn bundle_folder_of(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(i) => &path[..=i],
+        None => "",
+    }
+}
+
+fn sha1_hex(contents: &[u8]) -> String {
+    Sha1::digest(contents)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{Read, Write},
+    };
+
+    use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+    use crate::{
+        patching::{
+            bundle::{MANIFEST_FILE_NAME, PatchBundle, create_bundle},
+            signing::{Keyring, SigningKeyFile, Trust, sign_patch_set},
+        },
+        test_util::{temp_folder, write_music_swap_patch_set},
+    };
+
+    #[test]
+    fn bundles_round_trip() {
+        let folder = temp_folder("bundles_round_trip");
+        let bundle_path = folder.join("ghoulies.xbpatch");
+
+        let manifest = create_bundle(&write_music_swap_patch_set(&folder), &bundle_path).unwrap();
+        assert_eq!(manifest.files.len(), 2);
+
+        let mut bundle = PatchBundle::open(&bundle_path).unwrap();
+        assert_eq!(bundle.validate().unwrap(), vec![]);
+
+        let extracted = bundle.extract(&folder.join("extracted")).unwrap();
+
+        assert_eq!(extracted, folder.join("extracted/ghoulies.json"));
+        assert_eq!(
+            std::fs::read(folder.join("extracted/assets/title.wma")).unwrap(),
+            b"music"
+        );
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn extracting_doesnt_overwrite_files() {
+        let folder = temp_folder("extracting_doesnt_overwrite_files");
+        let bundle_path = folder.join("ghoulies.xbpatch");
+        create_bundle(&write_music_swap_patch_set(&folder), &bundle_path).unwrap();
+
+        let extracted = folder.join("extracted");
+        std::fs::create_dir_all(extracted.join("assets")).unwrap();
+        std::fs::write(extracted.join("assets/title.wma"), b"my music").unwrap();
+
+        let mut bundle = PatchBundle::open(&bundle_path).unwrap();
+        assert!(bundle.extract(&extracted).is_err());
+
+        assert_eq!(
+            std::fs::read(extracted.join("assets/title.wma")).unwrap(),
+            b"my music"
+        );
+        assert!(!extracted.join("ghoulies.json").exists());
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn files_larger_than_the_manifest_says_arent_read() {
+        let folder = temp_folder("files_larger_than_the_manifest_says_arent_read");
+        let bundle_path = folder.join("ghoulies.xbpatch");
+        let mut manifest =
+            create_bundle(&write_music_swap_patch_set(&folder), &bundle_path).unwrap();
+
+        // Rebuilt with a manifest that claims the music is smaller than it is
+        let mut original = ZipArchive::new(File::open(&bundle_path).unwrap()).unwrap();
+        let mut writer = ZipWriter::new(File::create(folder.join("smaller.xbpatch")).unwrap());
+
+        for file in &mut manifest.files {
+            let mut contents = Vec::new();
+            original
+                .by_name(&file.path)
+                .unwrap()
+                .read_to_end(&mut contents)
+                .unwrap();
+
+            writer
+                .start_file(file.path.as_str(), SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(&contents).unwrap();
+
+            if file.path == "assets/title.wma" {
+                file.size = 2;
+            }
+        }
+
+        writer
+            .start_file(MANIFEST_FILE_NAME, SimpleFileOptions::default())
+            .unwrap();
+        writer
+            .write_all(&serde_json::to_vec(&manifest).unwrap())
+            .unwrap();
+        writer.finish().unwrap();
+
+        let mut bundle = PatchBundle::open(&folder.join("smaller.xbpatch")).unwrap();
+
+        let paths: Vec<String> = bundle
+            .validate()
+            .unwrap()
+            .into_iter()
+            .map(|p| p.path)
+            .collect();
+        assert_eq!(paths, vec!["manifest.json:files[1].size"]);
+        assert!(bundle.extract(&folder.join("extracted")).is_err());
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn signatures_are_bundled() {
+        let folder = temp_folder("signatures_are_bundled");
+        let bundle_path = folder.join("ghoulies.xbpatch");
+        let patch_set_path = write_music_swap_patch_set(&folder);
+
+        let key = SigningKeyFile::generate("hethan".to_string()).unwrap();
+        let keyring = Keyring {
+            trusted: vec![key.trusted_key().unwrap()],
+        };
+
+        sign_patch_set(&patch_set_path, &key).unwrap();
+        create_bundle(&patch_set_path, &bundle_path).unwrap();
+
+        let mut bundle = PatchBundle::open(&bundle_path).unwrap();
+
+        assert_eq!(bundle.validate().unwrap(), vec![]);
+        assert_eq!(
+            bundle.verify(&keyring).unwrap(),
+            Trust::Trusted("hethan".to_string())
+        );
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn missing_assets_stop_bundling() {
+        let folder = temp_folder("missing_assets_stop_bundling");
+        let patch_set_path = write_music_swap_patch_set(&folder);
+
+        std::fs::remove_file(folder.join("assets/title.wma")).unwrap();
+
+        assert!(create_bundle(&patch_set_path, &folder.join("ghoulies.xbpatch")).is_err());
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+}
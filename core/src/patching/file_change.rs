@@ -0,0 +1,135 @@
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::game_files::is_game_file_path;
+
+/// Xbox discs store file sizes in 32 bits, so nothing larger can be added to a game.
+pub const MAX_FILE_SIZE: u64 = u32::MAX as u64;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+pub enum FileAction {
+    // Overwrites a file in the game with the source file
+    Replace,
+
+    // Copies the source file into the game, where no file exists yet
+    Add,
+
+    // Removes a file from the game
+    Delete,
+}
+
+/// A change to a whole file in the game, such as swapping a texture or music track for one that is
+/// shipped alongside the patch set.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct FileChange {
+    // The file in the game's folder, relative to default.xbe, eg. "media/music/title.wma"
+    pub path: String,
+
+    pub action: FileAction,
+
+    // The file to copy in for Replace and Add, relative to the patch set's JSON file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+
+    // Where the source was found on disk, filled in when the entry is added to a patch job
+    #[serde(skip)]
+    pub source_path: Option<PathBuf>,
+}
+
+impl FileChange {
+    /// Finds the source file relative to the folder its patch set was loaded from.
+    pub fn resolve_source(&mut self, folder: Option<&Path>) {
+        self.source_path = self
+            .source
+            .as_ref()
+            .map(|s| folder.unwrap_or(Path::new("")).join(s));
+    }
+
+    /// Checks the parts of the change that don't depend on any files, returning the name of the
+    /// field that is wrong along with the reason.
+    pub fn check(&self) -> Result<(), (&'static str, String)> {
+        if !is_game_file_path(&self.path) {
+            return Err((
+                "path",
+                format!("\"{}\" is not a path inside the game's folder", self.path),
+            ));
+        }
+
+        match (&self.source, self.action) {
+            (Some(_), FileAction::Delete) => Err((
+                "source",
+                "a file that is being deleted can't have a source".to_string(),
+            )),
+            (None, FileAction::Replace) => Err((
+                "source",
+                "a file can't be replaced without a source".to_string(),
+            )),
+            (None, FileAction::Add) => Err((
+                "source",
+                "a file can't be added without a source".to_string(),
+            )),
+            (Some(source), _) if !is_game_file_path(source) => Err((
+                "source",
+                format!("\"{}\" is not a path inside the patch set's folder", source),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::patching::file_change::{FileAction, FileChange};
+
+    fn change(action: FileAction, source: Option<&str>) -> FileChange {
+        FileChange {
+            path: "media/music/title.wma".to_string(),
+            action,
+            source: source.map(str::to_string),
+            source_path: None,
+        }
+    }
+
+    #[test]
+    fn sources_match_their_action() {
+        assert!(
+            change(FileAction::Replace, Some("music/title.wma"))
+                .check()
+                .is_ok()
+        );
+        assert!(change(FileAction::Delete, None).check().is_ok());
+
+        assert_eq!(
+            change(FileAction::Add, None).check(),
+            Err((
+                "source",
+                "a file can't be added without a source".to_string()
+            ))
+        );
+        assert!(
+            change(FileAction::Delete, Some("title.wma"))
+                .check()
+                .is_err()
+        );
+        assert!(
+            change(FileAction::Replace, Some("../title.wma"))
+                .check()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn sources_are_found_next_to_the_patch_set() {
+        let mut replace = change(FileAction::Replace, Some("music/title.wma"));
+        replace.resolve_source(Some(Path::new("patch_sets")));
+
+        assert_eq!(
+            replace.source_path,
+            Some(PathBuf::from("patch_sets/music/title.wma"))
+        );
+    }
+}
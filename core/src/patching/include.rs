@@ -0,0 +1,282 @@
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::patching::{PatchSet, patch_set::OptionGroup, symbol::Symbol};
+
+/// Another patch set whose entries are added to this one, such as a shared set of fixes for every
+/// game on one engine.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct PatchSetInclude {
+    // The included patch set's file, relative to this one, eg. "../engine_base.json"
+    pub path: String,
+
+    // Names of the included patch set's entries to leave out
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disable: Vec<String>,
+}
+
+/// Loads a patch set along with the entries of every patch set it includes.
+pub fn load_with_includes(path: &PathBuf) -> Result<PatchSet, std::io::Error> {
+    resolve_includes(PatchSet::from_file(path)?, path)
+}
+
+/// Merges in the entries, symbols, option groups and categories of every patch set that a patch
+/// set includes, and of any patch sets those include. Anything in a patch set replaces what it
+/// includes under the same name, and later includes replace earlier ones.
+pub fn resolve_includes(patch_set: PatchSet, path: &Path) -> Result<PatchSet, std::io::Error> {
+    resolve(patch_set, path, &mut Vec::new())
+}
+
+/// Every patch set file that a patch set includes, directly or through the patch sets it includes,
+/// in the order they are loaded. A patch set included more than once is only listed once.
+pub fn included_paths(path: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut paths = Vec::new();
+    let mut visited = vec![path.canonicalize()?];
+
+    collect_included_paths(path, &mut paths, &mut visited)?;
+
+    Ok(paths)
+}
+
+fn collect_included_paths(
+    path: &Path,
+    paths: &mut Vec<PathBuf>,
+    visited: &mut Vec<PathBuf>,
+) -> Result<(), std::io::Error> {
+    let folder = path.parent().unwrap_or(Path::new(""));
+
+    for include in PatchSet::from_file(&path.to_path_buf())?.includes {
+        let include_path = folder.join(&include.path);
+        let canonical = include_path.canonicalize()?;
+
+        if visited.contains(&canonical) {
+            continue;
+        }
+
+        visited.push(canonical);
+        paths.push(include_path.clone());
+
+        collect_included_paths(&include_path, paths, visited)?;
+    }
+
+    Ok(())
+}
+
+// The chain holds each patch set that is being resolved, so that one including itself is noticed
+fn resolve(
+    mut patch_set: PatchSet,
+    path: &Path,
+    chain: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<PatchSet, std::io::Error> {
+    if patch_set.includes.is_empty() {
+        return Ok(patch_set);
+    }
+
+    let canonical = path.canonicalize()?;
+
+    if let Some(start) = chain.iter().position(|(c, _)| *c == canonical) {
+        return Err(std::io::Error::other(format!(
+            "The patch sets include each other: {} -> {}.",
+            chain[start..]
+                .iter()
+                .map(|(_, p)| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" -> "),
+            path.display()
+        )));
+    }
+
+    chain.push((canonical, path.to_path_buf()));
+
+    let folder = path.parent().unwrap_or(Path::new(""));
+    let mut merged = PatchSet::default();
+
+    for include in &patch_set.includes {
+        let include_path = folder.join(&include.path);
+
+        let included = PatchSet::from_file(&include_path).map_err(|e| {
+            std::io::Error::other(format!(
+                "Unable to load the included patch set {}. Error: {}",
+                include_path.display(),
+                e
+            ))
+        })?;
+        let included = resolve(included, &include_path, chain)?;
+
+        for name in &include.disable {
+            if !included.entries.iter().any(|e| e.name() == name) {
+                return Err(std::io::Error::other(format!(
+                    "{} has no entry named \"{}\" to disable.",
+                    include_path.display(),
+                    name
+                )));
+            }
+        }
+
+        for mut entry in included.entries {
+            if include.disable.contains(entry.name()) {
+                continue;
+            }
+
+            // Entries keep the file they were first written in, however deeply they're included
+            entry
+                .included_from
+                .get_or_insert_with(|| include_path.clone());
+
+            replace_or_push(&mut merged.entries, entry, |e| e.name().clone());
+        }
+
+        merge(&mut merged, included.symbols, included.option_groups);
+        merged.categories.extend(included.categories);
+    }
+
+    for entry in std::mem::take(&mut patch_set.entries) {
+        replace_or_push(&mut merged.entries, entry, |e| e.name().clone());
+    }
+
+    merge(
+        &mut merged,
+        std::mem::take(&mut patch_set.symbols),
+        std::mem::take(&mut patch_set.option_groups),
+    );
+
+    // The patch set's own categories come first, since they set the order categories are listed in
+    for category in std::mem::take(&mut merged.categories) {
+        if !patch_set.categories.contains(&category) {
+            patch_set.categories.push(category);
+        }
+    }
+
+    patch_set.entries = merged.entries;
+    patch_set.symbols = merged.symbols;
+    patch_set.option_groups = merged.option_groups;
+
+    chain.pop();
+    Ok(patch_set)
+}
+
+fn merge(merged: &mut PatchSet, symbols: Vec<Symbol>, option_groups: Vec<OptionGroup>) {
+    for symbol in symbols {
+        replace_or_push(&mut merged.symbols, symbol, |s| s.name.clone());
+    }
+
+    for group in option_groups {
+        replace_or_push(&mut merged.option_groups, group, |g| g.name.clone());
+    }
+}
+
+// Replaces the item with the same name where it is, so overriding an entry doesn't move it
+fn replace_or_push<T>(items: &mut Vec<T>, item: T, name: impl Fn(&T) -> String) {
+    match items.iter().position(|i| name(i) == name(&item)) {
+        Some(i) => items[i] = item,
+        None => items.push(item),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::{
+        patching::include::load_with_includes,
+        test_util::{entry, patch_set_json, temp_folder},
+    };
+
+    fn write_patch_set(
+        folder: &Path,
+        file_name: &str,
+        includes: serde_json::Value,
+        entries: &[&str],
+    ) -> PathBuf {
+        let entries: Vec<serde_json::Value> = entries
+            .iter()
+            .map(|name| {
+                entry(
+                    name,
+                    serde_json::json!({
+                        "description": file_name,
+                        "patches": [
+                            { "offset": "0x1000", "offset_type": "Raw", "replacement_bytes": "0x90" }
+                        ]
+                    }),
+                )
+            })
+            .collect();
+
+        let mut patch_set = patch_set_json(file_name, entries.into());
+        patch_set["includes"] = includes;
+
+        let path = folder.join(file_name);
+        std::fs::write(&path, patch_set.to_string()).unwrap();
+
+        path
+    }
+
+    #[test]
+    fn included_entries_can_be_overridden_or_disabled() {
+        let folder = temp_folder("included_entries_can_be_overridden_or_disabled");
+
+        write_patch_set(
+            &folder,
+            "base.json",
+            serde_json::json!([]),
+            &["Uncap frame rate", "Skip intro", "Widescreen"],
+        );
+        let path = write_patch_set(
+            &folder,
+            "ghoulies.json",
+            serde_json::json!([{ "path": "base.json", "disable": ["Skip intro"] }]),
+            &["Widescreen", "Disable reaper spawn"],
+        );
+
+        let patch_set = load_with_includes(&path).unwrap();
+
+        let entries: Vec<(&str, &str, bool)> = patch_set
+            .entries
+            .iter()
+            .map(|e| {
+                (
+                    e.name().as_str(),
+                    e.description(),
+                    e.included_from().is_some(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            entries,
+            vec![
+                ("Uncap frame rate", "base.json", true),
+                ("Widescreen", "ghoulies.json", false),
+                ("Disable reaper spawn", "ghoulies.json", false),
+            ]
+        );
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn include_cycles_are_refused() {
+        let folder = temp_folder("include_cycles_are_refused");
+
+        write_patch_set(
+            &folder,
+            "base.json",
+            serde_json::json!([{ "path": "ghoulies.json" }]),
+            &["Uncap frame rate"],
+        );
+        let path = write_patch_set(
+            &folder,
+            "ghoulies.json",
+            serde_json::json!([{ "path": "base.json" }]),
+            &["Widescreen"],
+        );
+
+        let error = load_with_includes(&path).unwrap_err().to_string();
+        assert!(error.starts_with("The patch sets include each other"));
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+}
@@ -0,0 +1,524 @@
+use std::{collections::HashSet, path::Path};
+
+use crate::patching::{
+    EntryReference, PatchContents, PatchEntry, PatchSet,
+    random::{self, SeededRng},
+    selection::{self, SelectionError, SetSelection},
+};
+
+// An entry's position, as (patch set index, entry index)
+type EntryKey = (usize, usize);
+
+/// The entries chosen from every loaded patch set, checked against their option groups and
+/// requirements. Entries are ordered so that anything an entry requires is applied before it,
+/// parameters have been replaced with the bytes of their chosen values, random values have been
+/// picked, and symbols have been looked up in their patch sets.
+#[derive(Debug, Clone, Default)]
+pub struct PatchJob {
+    entries: Vec<PatchEntry>,
+
+    // The seed random values were picked with, if any patches have them
+    seed: Option<u64>,
+}
+
+impl PatchJob {
+    /// Builds a job from each patch set and what was chosen from it, picking random values with a
+    /// new seed. Every problem with the selection is returned if it can't be applied.
+    pub fn new(selections: &[(&PatchSet, &SetSelection)]) -> Result<PatchJob, Vec<SelectionError>> {
+        PatchJob::with_seed(selections, random::new_seed())
+    }
+
+    /// Builds a job like `new`, picking random values with a seed from an earlier job so that the
+    /// same values are written again.
+    pub fn with_seed(
+        selections: &[(&PatchSet, &SetSelection)],
+        seed: u64,
+    ) -> Result<PatchJob, Vec<SelectionError>> {
+        let mut errors: Vec<SelectionError> = selections
+            .iter()
+            .flat_map(|(patch_set, selection)| {
+                selection::validate_selection(patch_set, &selection.enabled)
+            })
+            .collect();
+
+        let graph = EntryGraph { selections, seed };
+
+        let enabled: Vec<EntryKey> = selections
+            .iter()
+            .enumerate()
+            .flat_map(|(s, (patch_set, _))| (0..patch_set.len()).map(move |e| (s, e)))
+            .filter(|key| graph.is_enabled(*key))
+            .collect();
+
+        let mut conflicts: HashSet<(EntryKey, EntryKey)> = HashSet::new();
+
+        for key in &enabled {
+            for reference in graph.entry(*key).requires() {
+                match graph.find(key.0, reference) {
+                    Some(required) if graph.is_enabled(required) => (),
+                    Some(required) => errors.push(SelectionError::MissingRequirement {
+                        entry: graph.describe(*key),
+                        requirement: graph.describe(required),
+                    }),
+                    None => errors.push(SelectionError::UnknownRequirement {
+                        entry: graph.describe(*key),
+                        requirement: graph.describe_reference(key.0, reference),
+                    }),
+                }
+            }
+
+            // References to entries that aren't loaded can't conflict with anything
+            for other in graph
+                .entry(*key)
+                .conflicts()
+                .iter()
+                .filter_map(|r| graph.find(key.0, r))
+                .filter(|other| graph.is_enabled(*other))
+            {
+                // Entries which name each other are only reported once
+                let pair = (*key.min(&other), *key.max(&other));
+
+                if conflicts.insert(pair) {
+                    errors.push(SelectionError::Conflict {
+                        entry: graph.describe(*key),
+                        conflicts_with: graph.describe(other),
+                    });
+                }
+            }
+        }
+
+        let mut ordered = Vec::new();
+        let mut visited = HashSet::new();
+
+        for key in &enabled {
+            graph.order_requirements_first(*key, &mut visited, &mut ordered);
+        }
+
+        let mut entries = Vec::new();
+        let mut is_random = false;
+
+        for key in ordered {
+            is_random |= graph
+                .entry(key)
+                .all_patches()
+                .any(|p| matches!(p.contents, PatchContents::Random { .. }));
+
+            match graph.resolve_patches(key) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => errors.extend(e),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(PatchJob {
+            entries,
+            seed: is_random.then_some(seed),
+        })
+    }
+
+    pub fn entries(&self) -> &[PatchEntry] {
+        &self.entries
+    }
+
+    /// The seed random values were picked with, or None if no enabled entry has random values.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn into_entries(self) -> Vec<PatchEntry> {
+        self.entries
+    }
+}
+
+struct EntryGraph<'a> {
+    selections: &'a [(&'a PatchSet, &'a SetSelection)],
+    seed: u64,
+}
+
+impl EntryGraph<'_> {
+    fn entry(&self, (s, e): EntryKey) -> &PatchEntry {
+        &self.selections[s].0.entries[e]
+    }
+
+    fn is_enabled(&self, (s, e): EntryKey) -> bool {
+        self.selections[s].1.is_enabled(e)
+    }
+
+    /// Copies an entry, replacing its parameter and random patches with the bytes of the chosen
+    /// values, and its symbols with their definitions. The sources of its file changes are found
+    /// relative to the patch set the entry was written in.
+    fn resolve_patches(&self, key: EntryKey) -> Result<PatchEntry, Vec<SelectionError>> {
+        let mut entry = self.entry(key).clone();
+        let mut errors = Vec::new();
+
+        let folder = match entry.included_from() {
+            Some(path) => path.parent().map(Path::to_path_buf),
+            None => self.selections[key.0].0.folder.clone(),
+        };
+
+        for change in entry.files_mut() {
+            change.resolve_source(folder.as_deref());
+        }
+
+        for (i, patch) in entry.all_patches_mut().enumerate() {
+            match self.selections[key.0].0.resolve_symbol(patch) {
+                Ok(resolved) => *patch = resolved,
+                Err(reason) => errors.push(SelectionError::UnknownSymbol {
+                    entry: self.describe(key),
+                    reason,
+                }),
+            }
+
+            if let PatchContents::Random { random } = &patch.contents {
+                let (patch_set, entry) = (&self.selections[key.0].0.name, self.entry(key).name());
+                let mut rng = SeededRng::for_patch(self.seed, patch_set, entry, i);
+
+                match random.pick(&mut rng) {
+                    Ok(bytes) => {
+                        patch.contents = PatchContents::Bytes {
+                            replacement_bytes: bytes,
+                        }
+                    }
+                    Err(reason) => errors.push(SelectionError::InvalidRandom {
+                        entry: self.describe(key),
+                        reason,
+                    }),
+                }
+            }
+
+            let PatchContents::Parameter { parameter: name } = &patch.contents else {
+                continue;
+            };
+
+            let encoded = match self.entry(key).parameter(name) {
+                Some(parameter) => {
+                    parameter.encode(self.selections[key.0].1.value(key.1, parameter))
+                }
+                None => Err("the entry has no parameter with this name".to_string()),
+            };
+
+            match encoded {
+                Ok(bytes) => {
+                    patch.contents = PatchContents::Bytes {
+                        replacement_bytes: bytes,
+                    }
+                }
+                Err(reason) => errors.push(SelectionError::InvalidParameter {
+                    entry: self.describe(key),
+                    parameter: name.clone(),
+                    reason,
+                }),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(entry)
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn find(&self, from_set: usize, reference: &EntryReference) -> Option<EntryKey> {
+        let s = match reference.patch_set() {
+            Some(name) => self.selections.iter().position(|(ps, _)| ps.name == name)?,
+            None => from_set,
+        };
+
+        let e = self.selections[s]
+            .0
+            .entries
+            .iter()
+            .position(|entry| entry.name() == reference.entry())?;
+
+        Some((s, e))
+    }
+
+    fn describe(&self, key: EntryKey) -> String {
+        format!(
+            "\"{}\" ({})",
+            self.entry(key).name(),
+            self.selections[key.0].0.name
+        )
+    }
+
+    fn describe_reference(&self, from_set: usize, reference: &EntryReference) -> String {
+        format!(
+            "\"{}\" ({})",
+            reference.entry(),
+            reference
+                .patch_set()
+                .unwrap_or(&self.selections[from_set].0.name)
+        )
+    }
+
+    // Depth first, so that enabled requirements end up before the entries that need them. Cycles
+    // are broken at the first entry that is visited twice.
+    fn order_requirements_first(
+        &self,
+        key: EntryKey,
+        visited: &mut HashSet<EntryKey>,
+        ordered: &mut Vec<EntryKey>,
+    ) {
+        if !visited.insert(key) {
+            return;
+        }
+
+        for required in self
+            .entry(key)
+            .requires()
+            .iter()
+            .filter_map(|r| self.find(key.0, r))
+            .filter(|r| self.is_enabled(*r))
+        {
+            self.order_requirements_first(required, visited, ordered);
+        }
+
+        ordered.push(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        HasPatches,
+        patching::{
+            PatchContents, PatchOffsetType, PatchSet,
+            job::PatchJob,
+            selection::{SelectionError, SetSelection},
+            symbol::Symbol,
+        },
+        test_util::{entry, patch_set},
+    };
+
+    fn enabled(enabled: &[bool]) -> SetSelection {
+        SetSelection {
+            enabled: enabled.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    fn get_ghoulies() -> PatchSet {
+        patch_set(
+            "ghoulies",
+            serde_json::json!([
+                entry(
+                    "Randomise replay scene",
+                    serde_json::json!({
+                        "requires": ["Randomise replay scene 2"],
+                        "patches": []
+                    }),
+                ),
+                entry("Randomise replay scene 2", serde_json::json!({})),
+                entry(
+                    "Force 1sec cutscenes",
+                    serde_json::json!({
+                        "conflicts": ["Force 1.2sec cutscenes"],
+                        "patches": []
+                    }),
+                ),
+                entry(
+                    "Force 1.2sec cutscenes",
+                    serde_json::json!({
+                        "conflicts": ["Force 1sec cutscenes"],
+                        "patches": []
+                    }),
+                ),
+            ]),
+        )
+    }
+
+    #[test]
+    fn requirements_are_applied_first() {
+        let ghoulies = get_ghoulies();
+        let selection = enabled(&[true, true, false, false]);
+
+        let job = PatchJob::new(&[(&ghoulies, &selection)]).unwrap();
+        let names: Vec<&String> = job.entries().iter().map(|e| e.name()).collect();
+
+        assert_eq!(
+            names,
+            vec!["Randomise replay scene 2", "Randomise replay scene"]
+        );
+    }
+
+    #[test]
+    fn explains_missing_requirements_and_conflicts() {
+        let ghoulies = get_ghoulies();
+        let selection = enabled(&[true, false, true, true]);
+
+        let errors = PatchJob::new(&[(&ghoulies, &selection)]).unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![
+                SelectionError::MissingRequirement {
+                    entry: "\"Randomise replay scene\" (ghoulies)".to_string(),
+                    requirement: "\"Randomise replay scene 2\" (ghoulies)".to_string(),
+                },
+                SelectionError::Conflict {
+                    entry: "\"Force 1sec cutscenes\" (ghoulies)".to_string(),
+                    conflicts_with: "\"Force 1.2sec cutscenes\" (ghoulies)".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_references_to_other_sets() {
+        let ghoulies = get_ghoulies();
+        let addon = patch_set(
+            "addon",
+            serde_json::json!([entry(
+                "Extra scenes",
+                serde_json::json!({
+                    "requires": [
+                        { "patch_set": "ghoulies", "entry": "Randomise replay scene 2" },
+                        { "patch_set": "missing", "entry": "Anything" }
+                    ],
+                    "patches": []
+                }),
+            )]),
+        );
+
+        let errors = PatchJob::new(&[
+            (&ghoulies, &enabled(&[false, true, false, false])),
+            (&addon, &enabled(&[true])),
+        ])
+        .unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![SelectionError::UnknownRequirement {
+                entry: "\"Extra scenes\" (addon)".to_string(),
+                requirement: "\"Anything\" (missing)".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parameters_are_replaced_with_their_values() {
+        let frame_cap = patch_set(
+            "frame cap",
+            serde_json::json!([entry(
+                "Frame cap",
+                serde_json::json!({
+                    "parameters": [
+                        { "name": "FPS", "value_type": "U16", "default": 30, "min": 15, "max": 240 }
+                    ],
+                    "patches": [
+                        { "offset": "0x1000", "offset_type": "Raw", "parameter": "FPS" }
+                    ]
+                }),
+            )]),
+        );
+
+        let mut selection = enabled(&[true]);
+
+        let job = PatchJob::new(&[(&frame_cap, &selection)]).unwrap();
+        assert_eq!(
+            job.entries()[0].get_patches()[0].contents,
+            PatchContents::Bytes {
+                replacement_bytes: vec![30, 0]
+            }
+        );
+
+        selection.set_value(0, "FPS", 300.0);
+
+        let errors = PatchJob::new(&[(&frame_cap, &selection)]).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![SelectionError::InvalidParameter {
+                entry: "\"Frame cap\" (frame cap)".to_string(),
+                parameter: "FPS".to_string(),
+                reason: "300 is more than the maximum of 240".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn random_values_are_picked_from_the_seed() {
+        let replay = patch_set(
+            "replay",
+            serde_json::json!([
+                entry(
+                    "Random replay scene",
+                    serde_json::json!({
+                        "patches": [
+                            { "offset": "0x1000", "offset_type": "Raw", "random": { "choices": ["0x01", "0x04", "0x07"] } },
+                            { "offset": "0x2000", "offset_type": "Raw", "random": { "min": 1, "max": 100, "value_type": "U32" } }
+                        ]
+                    }),
+                ),
+                entry("Skip intro", serde_json::json!({}))
+            ]),
+        );
+
+        let bytes = |selection: &[bool], seed| {
+            let job = PatchJob::with_seed(&[(&replay, &enabled(selection))], seed).unwrap();
+            assert_eq!(job.seed(), Some(seed));
+
+            job.entries()[0]
+                .get_patches()
+                .iter()
+                .map(|p| p.contents.clone())
+                .collect::<Vec<PatchContents>>()
+        };
+
+        let picked = bytes(&[true, false], 42);
+        assert!(
+            picked
+                .iter()
+                .all(|c| matches!(c, PatchContents::Bytes { .. }))
+        );
+
+        // Enabling other entries doesn't change what is picked
+        assert_eq!(picked, bytes(&[true, true], 42));
+
+        let job = PatchJob::with_seed(&[(&replay, &enabled(&[false, true]))], 42).unwrap();
+        assert_eq!(job.seed(), None);
+    }
+
+    #[test]
+    fn symbols_are_looked_up_in_their_patch_set() {
+        let mut replay = patch_set(
+            "replay",
+            serde_json::json!([entry(
+                "Randomise replay scene",
+                serde_json::json!({
+                    "patches": [
+                        {
+                            "offset": "0x6",
+                            "offset_type": { "Symbol": "PickReplayScene" },
+                            "replacement_bytes": "0x90"
+                        }
+                    ]
+                }),
+            )]),
+        );
+
+        let errors = PatchJob::new(&[(&replay, &enabled(&[true]))]).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![SelectionError::UnknownSymbol {
+                entry: "\"Randomise replay scene\" (replay)".to_string(),
+                reason: "the patch set has no symbol named \"PickReplayScene\"".to_string(),
+            }]
+        );
+
+        let symbol = Symbol {
+            name: "PickReplayScene".to_string(),
+            address: 0x37907d,
+            revisions: Vec::new(),
+        };
+        replay.symbols.push(symbol.clone());
+
+        let job = PatchJob::new(&[(&replay, &enabled(&[true]))]).unwrap();
+        assert!(matches!(
+            &job.entries()[0].get_patches()[0].offset_type,
+            PatchOffsetType::ResolvedSymbol(s) if *s == symbol
+        ));
+    }
+}
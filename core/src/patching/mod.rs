@@ -1,24 +1,97 @@
-use serde::{Deserialize, Serialize};
+use std::{fmt::Display, path::PathBuf};
 
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, de::Error as _};
+use serde_json::{Map, Value};
+
+pub mod bundle;
+pub mod file_change;
+pub mod include;
+pub mod job;
+pub mod operation;
+pub mod overlap;
+pub mod parameter;
+pub mod patch_set;
+pub mod random;
+pub mod schema;
+pub mod selection;
+pub mod selection_code;
 pub mod serialization;
+pub mod signature;
+pub mod signing;
+pub mod symbol;
+pub mod target;
+pub mod text;
+pub mod translation;
+pub mod validation;
 
+use file_change::FileChange;
+use parameter::EntryParameter;
+pub use patch_set::PatchSet;
+use random::RandomValues;
 use serialization::*;
+use signature::BytePattern;
+use symbol::Symbol;
+use target::BuildDetails;
+use text::{TextEncoding, TextPadding};
+use translation::Translations;
+
+use crate::xbe::XBEIdentity;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub enum PatchOffsetType {
     Raw,
     Virtual,
+
+    // Found by searching for a pattern of bytes, with the offset counted from the start of the
+    // match. The pattern must match exactly once, in the named section or anywhere in the XBE.
+    Signature {
+        pattern: BytePattern,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        section: Option<String>,
+
+        // Moves the start of the match, so a patch can be before the pattern that finds it
+        #[serde(default, skip_serializing_if = "is_zero")]
+        displacement: i32,
+    },
+
+    // Found by searching for a null terminated string that is already in the XBE, with the offset
+    // counted from its first character. Like a signature, the string must be found exactly once.
+    Text {
+        text: String,
+
+        #[serde(default)]
+        encoding: TextEncoding,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        section: Option<String>,
+    },
+
+    // Counted from the address of a symbol in the patch set's symbol table
+    Symbol(String),
+
+    // A symbol that has been looked up in its patch set, ready to be placed in a specific build
+    #[serde(skip)]
+    ResolvedSymbol(Symbol),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// A change to one location in the XBE.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct Patch {
     #[serde(serialize_with = "se_u32_to_hex", deserialize_with = "de_hex_to_u32")]
+    #[schemars(schema_with = "hex_u32_schema")]
     pub offset: u32,
 
     pub offset_type: PatchOffsetType,
 
-    #[serde(serialize_with = "se_vu8_to_hex", deserialize_with = "de_hex_to_vu8")]
-    pub replacement_bytes: Vec<u8>,
+    // Another file in the game's folder to patch, relative to default.xbe, eg. "media/config.ini".
+    // Only Raw offsets can be used in files that aren't XBEs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+
+    #[serde(flatten)]
+    pub contents: PatchContents,
 
     #[serde(
         serialize_with = "se_ovu8_to_hex",
@@ -26,25 +99,376 @@ pub struct Patch {
         skip_serializing_if = "Option::is_none",
         default = "get_none"
     )]
+    #[schemars(schema_with = "hex_bytes_schema")]
     pub original_bytes: Option<Vec<u8>>,
 }
 
+/// What a patch writes. Each kind is chosen by which of its fields are present in the patch.
+#[derive(Debug, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PatchContents {
+    Bytes {
+        #[serde(serialize_with = "se_vu8_to_hex")]
+        #[schemars(schema_with = "hex_bytes_schema")]
+        replacement_bytes: Vec<u8>,
+    },
+    // Writes the value of one of the entry's parameters
+    Parameter {
+        parameter: String,
+    },
+    // x86 assembly, one instruction per line, assembled at the patch's address when applied
+    Assembly {
+        assembly: Vec<String>,
+
+        // The most bytes the assembled code may take up. Defaults to the length of original_bytes.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_size: Option<usize>,
+    },
+    // Repeats a pattern of bytes over `length` bytes, eg. "0x90" for a run of nops
+    Fill {
+        #[serde(serialize_with = "se_vu8_to_hex")]
+        #[schemars(schema_with = "hex_bytes_schema")]
+        fill: Vec<u8>,
+        length: usize,
+    },
+    // A 5 byte relative jump to a virtual address
+    Jump {
+        #[serde(serialize_with = "se_u32_to_hex")]
+        #[schemars(schema_with = "hex_u32_schema")]
+        jump_to: u32,
+    },
+    // A 5 byte relative call to a function at a virtual address
+    Call {
+        #[serde(serialize_with = "se_u32_to_hex")]
+        #[schemars(schema_with = "hex_u32_schema")]
+        call_to: u32,
+    },
+    // A 4 byte absolute pointer to a virtual address
+    Pointer {
+        #[serde(serialize_with = "se_u32_to_hex")]
+        #[schemars(schema_with = "hex_u32_schema")]
+        pointer_to: u32,
+    },
+    // Copies `length` bytes from a virtual address in the XBE, as they are when the patch is applied
+    Copy {
+        #[serde(serialize_with = "se_u32_to_hex")]
+        #[schemars(schema_with = "hex_u32_schema")]
+        copy_from: u32,
+        length: usize,
+    },
+    // A string, padded to fill the space of the one it replaces. Fails to apply if it doesn't fit.
+    Text {
+        text: String,
+
+        #[serde(default)]
+        encoding: TextEncoding,
+
+        #[serde(default)]
+        padding: TextPadding,
+
+        // The most bytes the text may take up. Defaults to the length of the string found by a
+        // Text offset, or else the length of original_bytes.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_length: Option<usize>,
+    },
+    // A value picked using the job's seed, eg. a random starting level that is the same for
+    // everyone given the same seed
+    Random {
+        random: RandomValues,
+    },
+}
+
+// The field that picks each kind of contents, and the other fields that kind can have
+const CONTENT_FIELDS: [(&str, &[&str]); 10] = [
+    ("replacement_bytes", &[]),
+    ("parameter", &[]),
+    ("assembly", &["max_size"]),
+    ("fill", &["length"]),
+    ("jump_to", &[]),
+    ("call_to", &[]),
+    ("pointer_to", &[]),
+    ("copy_from", &["length"]),
+    ("text", &["encoding", "padding", "max_length"]),
+    ("random", &[]),
+];
+
+// Contents are read by hand rather than as an untagged enum, so that a mistake in one field is
+// explained instead of being reported as "data did not match any variant", and so that patches
+// with the fields of more than one kind are refused.
+impl<'de> Deserialize<'de> for PatchContents {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut fields = Map::<String, Value>::deserialize(deserializer)?;
+
+        let kinds: Vec<&(&str, &[&str])> = CONTENT_FIELDS
+            .iter()
+            .filter(|(kind, _)| fields.contains_key(*kind))
+            .collect();
+
+        let (kind, others) = match kinds.as_slice() {
+            [] => {
+                return Err(D::Error::custom(format!(
+                    "a patch needs one of {}",
+                    CONTENT_FIELDS.map(|(kind, _)| kind).join(", ")
+                )));
+            }
+            [kind] => **kind,
+            _ => {
+                return Err(D::Error::custom(format!(
+                    "a patch can only have one of {}",
+                    kinds
+                        .iter()
+                        .map(|(kind, _)| *kind)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            }
+        };
+
+        if let Some(field) = CONTENT_FIELDS
+            .iter()
+            .flat_map(|(_, others)| others.iter())
+            .find(|field| fields.contains_key(**field) && !others.contains(field))
+        {
+            return Err(D::Error::custom(format!(
+                "{} can't be used in a patch with {}",
+                field, kind
+            )));
+        }
+
+        let fields = &mut fields;
+
+        Ok(match kind {
+            "replacement_bytes" => PatchContents::Bytes {
+                replacement_bytes: content_field(fields, kind, de_hex_to_vu8)?,
+            },
+            "parameter" => PatchContents::Parameter {
+                parameter: content_field(fields, kind, String::deserialize)?,
+            },
+            "assembly" => PatchContents::Assembly {
+                assembly: content_field(fields, kind, Vec::deserialize)?,
+                max_size: optional_content_field(fields, "max_size", Option::deserialize)?,
+            },
+            "fill" => PatchContents::Fill {
+                fill: content_field(fields, kind, de_hex_to_vu8)?,
+                length: content_field(fields, "length", usize::deserialize)?,
+            },
+            "jump_to" => PatchContents::Jump {
+                jump_to: content_field(fields, kind, de_hex_to_u32)?,
+            },
+            "call_to" => PatchContents::Call {
+                call_to: content_field(fields, kind, de_hex_to_u32)?,
+            },
+            "pointer_to" => PatchContents::Pointer {
+                pointer_to: content_field(fields, kind, de_hex_to_u32)?,
+            },
+            "copy_from" => PatchContents::Copy {
+                copy_from: content_field(fields, kind, de_hex_to_u32)?,
+                length: content_field(fields, "length", usize::deserialize)?,
+            },
+            "text" => PatchContents::Text {
+                text: content_field(fields, kind, String::deserialize)?,
+                encoding: optional_content_field(fields, "encoding", TextEncoding::deserialize)?,
+                padding: optional_content_field(fields, "padding", TextPadding::deserialize)?,
+                max_length: optional_content_field(fields, "max_length", Option::deserialize)?,
+            },
+            _ => PatchContents::Random {
+                random: content_field(fields, kind, RandomValues::deserialize)?,
+            },
+        })
+    }
+}
+
+fn content_field<T, E: serde::de::Error>(
+    fields: &mut Map<String, Value>,
+    name: &str,
+    read: impl FnOnce(Value) -> Result<T, serde_json::Error>,
+) -> Result<T, E> {
+    let value = fields
+        .remove(name)
+        .ok_or(E::custom(format!("missing field `{}`", name)))?;
+
+    read(value).map_err(|e| E::custom(format!("{}: {}", name, e)))
+}
+
+fn optional_content_field<T: Default, E: serde::de::Error>(
+    fields: &mut Map<String, Value>,
+    name: &str,
+    read: impl FnOnce(Value) -> Result<T, serde_json::Error>,
+) -> Result<T, E> {
+    match fields.contains_key(name) {
+        true => content_field(fields, name, read),
+        false => Ok(T::default()),
+    }
+}
+
+impl PatchContents {
+    /// The bytes written by contents that don't depend on where they're written, or on the XBE.
+    pub fn fixed_bytes(&self) -> Option<Vec<u8>> {
+        match self {
+            PatchContents::Bytes { replacement_bytes } => Some(replacement_bytes.clone()),
+            PatchContents::Fill { fill, length } => operation::fill(fill, *length).ok(),
+            PatchContents::Pointer { pointer_to } => Some(operation::pointer(*pointer_to)),
+            _ => None,
+        }
+    }
+}
+
+/// A patch that has been reduced to the exact bytes it will write, and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedPatch {
+    // The file being written to, or None for default.xbe
+    pub file: Option<String>,
+    pub file_offset: u64,
+    pub bytes: Vec<u8>,
+}
+
 pub trait HasPatches {
     fn add_patch(&mut self, patch: Patch);
     fn get_patches(&self) -> &Vec<Patch>;
+    fn get_patches_mut(&mut self) -> &mut Vec<Patch>;
 
     fn set_patches(&mut self, patch: Vec<Patch>) -> Result<(), Box<dyn std::error::Error>>;
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Refers to an entry by name, either in the same patch set or in another patch set.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum EntryReference {
+    SameSet(String),
+    OtherSet { patch_set: String, entry: String },
+}
+
+impl EntryReference {
+    pub fn entry(&self) -> &str {
+        match self {
+            EntryReference::SameSet(entry) => entry,
+            EntryReference::OtherSet { entry, .. } => entry,
+        }
+    }
+
+    /// The name of the patch set being referred to, or `None` for the patch set of the entry that
+    /// holds the reference.
+    pub fn patch_set(&self) -> Option<&str> {
+        match self {
+            EntryReference::SameSet(_) => None,
+            EntryReference::OtherSet { patch_set, .. } => Some(patch_set),
+        }
+    }
+}
+
+/// Patches that replace an entry's usual patches on one build of the game, such as a different
+/// region or title update where the same change is at different offsets.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct PatchVariant {
+    #[serde(flatten)]
+    pub build: BuildDetails,
+
+    pub patches: Vec<Patch>,
+}
+
+/// How well an entry is known to work.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq)]
+pub enum EntryStatus {
+    #[default]
+    Stable,
+    Experimental,
+    Broken,
+    CausesSoftlock,
+}
+
+impl EntryStatus {
+    pub fn is_stable(&self) -> bool {
+        *self == EntryStatus::Stable
+    }
+
+    /// Whether the entry is known to stop the game from being played normally.
+    pub fn is_broken(&self) -> bool {
+        matches!(self, EntryStatus::Broken | EntryStatus::CausesSoftlock)
+    }
+}
+
+impl Display for EntryStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            EntryStatus::Stable => "Stable",
+            EntryStatus::Experimental => "Experimental",
+            EntryStatus::Broken => "Broken",
+            EntryStatus::CausesSoftlock => "Causes softlock",
+        };
+
+        write!(f, "{}", text)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct PatchEntry {
     name: String,
     description: String,
 
+    // The name and description in other languages, keyed by language code, eg. "ja"
+    #[serde(default, skip_serializing_if = "Translations::is_empty")]
+    translations: Translations,
+
     // Specified if another author made a specific patch in a patch list
     alt_author: Option<String>,
 
+    // Entries sharing a group are alternatives for the same setting (see OptionGroup)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+
+    // The heading the entry is listed under, eg. "Camera"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    category: Option<String>,
+
+    // Short labels describing the entry, eg. "speedrun"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+
+    // Entries with an order are listed first within their category, lowest first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    order: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "EntryStatus::is_stable")]
+    status: EntryStatus,
+
+    // Problems users should know about before enabling the entry
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    known_issues: Vec<String>,
+
+    // The entry's own version, eg. "1.2", for telling apart changes to a single entry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+
+    // Other entries which must be enabled alongside this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    requires: Vec<EntryReference>,
+
+    // Other entries which can't be enabled alongside this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    conflicts: Vec<EntryReference>,
+
+    // Values chosen by the user, which patches in this entry can write
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    parameters: Vec<EntryParameter>,
+
+    // Can be left out of entries that only change whole files
+    #[serde(default)]
     patches: Vec<Patch>,
+
+    // Used instead of patches on the builds they match. The first matching variant is used.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    variants: Vec<PatchVariant>,
+
+    // Whole files to replace, add or delete in the game
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    files: Vec<FileChange>,
+
+    // The patch set file the entry was included from, or None if it's from the patch set itself
+    #[serde(skip)]
+    included_from: Option<PathBuf>,
 }
 
 impl PatchEntry {
@@ -57,8 +481,22 @@ impl PatchEntry {
         PatchEntry {
             name,
             description,
+            translations: Translations::new(),
             alt_author,
+            group: None,
+            category: None,
+            tags: Vec::new(),
+            order: None,
+            status: EntryStatus::Stable,
+            known_issues: Vec::new(),
+            version: None,
+            requires: Vec::new(),
+            conflicts: Vec::new(),
+            parameters: Vec::new(),
             patches,
+            variants: Vec::new(),
+            files: Vec::new(),
+            included_from: None,
         }
     }
 
@@ -69,6 +507,168 @@ impl PatchEntry {
     pub fn description(&self) -> &str {
         &self.description
     }
+
+    pub fn included_from(&self) -> Option<&PathBuf> {
+        self.included_from.as_ref()
+    }
+
+    pub fn translations(&self) -> &Translations {
+        &self.translations
+    }
+
+    /// The entry's name in a language, or its untranslated name if there is no translation. The
+    /// untranslated name is still what other entries and selections refer to the entry by.
+    pub fn localised_name(&self, language: Option<&str>) -> &str {
+        translation::localise(
+            &self.translations,
+            language,
+            |t| t.name.as_ref(),
+            &self.name,
+        )
+    }
+
+    pub fn localised_description(&self, language: Option<&str>) -> &str {
+        translation::localise(
+            &self.translations,
+            language,
+            |t| t.description.as_ref(),
+            &self.description,
+        )
+    }
+
+    pub fn alt_author(&self) -> Option<&str> {
+        self.alt_author.as_deref()
+    }
+
+    pub fn status(&self) -> EntryStatus {
+        self.status
+    }
+
+    pub fn known_issues(&self) -> &[String] {
+        &self.known_issues
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    pub fn category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn order(&self) -> Option<u32> {
+        self.order
+    }
+
+    pub fn requires(&self) -> &[EntryReference] {
+        &self.requires
+    }
+
+    pub fn conflicts(&self) -> &[EntryReference] {
+        &self.conflicts
+    }
+
+    pub fn parameters(&self) -> &[EntryParameter] {
+        &self.parameters
+    }
+
+    pub fn variants(&self) -> &[PatchVariant] {
+        &self.variants
+    }
+
+    pub fn files(&self) -> &[FileChange] {
+        &self.files
+    }
+
+    pub fn files_mut(&mut self) -> &mut Vec<FileChange> {
+        &mut self.files
+    }
+
+    fn variant_for(&self, xbe: &XBEIdentity) -> Option<usize> {
+        self.variants.iter().position(|v| v.build.matches(xbe))
+    }
+
+    /// The patches to apply to a build of the game.
+    pub fn patches_for(&self, xbe: &XBEIdentity) -> &Vec<Patch> {
+        match self.variant_for(xbe) {
+            Some(i) => &self.variants[i].patches,
+            None => &self.patches,
+        }
+    }
+
+    pub fn patches_for_mut(&mut self, xbe: &XBEIdentity) -> &mut Vec<Patch> {
+        match self.variant_for(xbe) {
+            Some(i) => &mut self.variants[i].patches,
+            None => &mut self.patches,
+        }
+    }
+
+    /// Every list of patches in the entry, including those of its variants.
+    pub fn all_patches(&self) -> impl Iterator<Item = &Patch> {
+        self.patches
+            .iter()
+            .chain(self.variants.iter().flat_map(|v| v.patches.iter()))
+    }
+
+    pub fn all_patches_mut(&mut self) -> impl Iterator<Item = &mut Patch> {
+        self.patches
+            .iter_mut()
+            .chain(self.variants.iter_mut().flat_map(|v| v.patches.iter_mut()))
+    }
+
+    /// Replaces the entry's patches with those for a build of the game, and removes its variants.
+    /// Returns false if the entry has variants but none of them are for this build, in which case
+    /// its usual patches are kept. Fails if it has no usual patches either, since it would then
+    /// change nothing on this build.
+    pub fn select_build(&mut self, xbe: &XBEIdentity) -> Result<bool, String> {
+        if self.variants.is_empty() {
+            return Ok(true);
+        }
+
+        let variant = self.variant_for(xbe);
+
+        if let Some(i) = variant {
+            self.patches = self.variants.swap_remove(i).patches;
+        } else if self.patches.is_empty() {
+            return Err(format!(
+                "\"{}\" has no patches for this build of the game ({}).",
+                self.name, xbe
+            ));
+        }
+
+        self.variants.clear();
+        Ok(variant.is_some())
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<&EntryParameter> {
+        self.parameters.iter().find(|p| p.name == name)
+    }
+
+    /// The number of bytes a patch in this entry writes, if it can be known without an XBE.
+    pub fn patch_len(&self, patch: &Patch) -> Option<usize> {
+        match &patch.contents {
+            PatchContents::Bytes { replacement_bytes } => Some(replacement_bytes.len()),
+            PatchContents::Parameter { parameter } => {
+                self.parameter(parameter).map(|p| p.value_type.size())
+            }
+            PatchContents::Assembly { max_size, .. } => *max_size,
+            PatchContents::Fill { length, .. } | PatchContents::Copy { length, .. } => {
+                Some(*length)
+            }
+            PatchContents::Jump { .. } | PatchContents::Call { .. } => Some(operation::BRANCH_LEN),
+            PatchContents::Pointer { pointer_to } => Some(operation::pointer(*pointer_to).len()),
+            PatchContents::Text { max_length, .. } => text::available_length(patch, *max_length),
+            PatchContents::Random { random } => random.size(),
+        }
+    }
 }
 
 impl HasPatches for PatchEntry {
@@ -76,6 +676,10 @@ impl HasPatches for PatchEntry {
         &self.patches
     }
 
+    fn get_patches_mut(&mut self) -> &mut Vec<Patch> {
+        &mut self.patches
+    }
+
     fn add_patch(&mut self, patch: Patch) {
         self.patches.push(patch);
     }
@@ -85,3 +689,111 @@ impl HasPatches for PatchEntry {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        HasPatches,
+        patching::{EntryStatus, PatchContents, PatchEntry},
+        xbe::XBEIdentity,
+    };
+
+    fn ghoulies(version: u32) -> XBEIdentity {
+        XBEIdentity {
+            title_id: 0x4d530051,
+            version,
+            region: 0x4,
+            sha1: String::new(),
+        }
+    }
+
+    #[test]
+    fn variants_replace_patches_on_their_build() {
+        let entry: PatchEntry = serde_json::from_value(serde_json::json!({
+            "name": "Disable reaper spawn",
+            "description": "",
+            "alt_author": null,
+            "patches": [
+                { "offset": "0xd0d50", "offset_type": "Virtual", "replacement_bytes": "0x9090909090" }
+            ],
+            "variants": [
+                {
+                    "version": 2,
+                    "patches": [
+                        { "offset": "0xd0e10", "offset_type": "Virtual", "replacement_bytes": "0x9090909090" }
+                    ]
+                }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(entry.patches_for(&ghoulies(1))[0].offset, 0xd0d50);
+        assert_eq!(entry.patches_for(&ghoulies(2))[0].offset, 0xd0e10);
+
+        let mut selected = entry.clone();
+        assert_eq!(selected.select_build(&ghoulies(2)), Ok(true));
+        assert_eq!(selected.get_patches()[0].offset, 0xd0e10);
+        assert!(selected.variants().is_empty());
+
+        let mut unknown = entry.clone();
+        assert_eq!(unknown.select_build(&ghoulies(3)), Ok(false));
+        assert_eq!(unknown.get_patches()[0].offset, 0xd0d50);
+
+        // Without usual patches there is nothing to fall back on
+        let mut only_variants = entry.clone();
+        only_variants.patches.clear();
+        assert!(only_variants.select_build(&ghoulies(3)).is_err());
+        assert_eq!(only_variants.clone().select_build(&ghoulies(2)), Ok(true));
+    }
+
+    #[test]
+    fn stable_status_is_left_out() {
+        let mut entry = PatchEntry::new(
+            "Force 1sec cutscenes".to_string(),
+            String::new(),
+            None,
+            Vec::new(),
+        );
+
+        assert_eq!(entry.status(), EntryStatus::Stable);
+        assert!(
+            serde_json::to_value(&entry)
+                .unwrap()
+                .get("status")
+                .is_none()
+        );
+
+        entry.status = EntryStatus::CausesSoftlock;
+
+        let value = serde_json::to_value(&entry).unwrap();
+        assert_eq!(value["status"], "CausesSoftlock");
+        assert!(entry.status().is_broken());
+    }
+
+    #[test]
+    fn contents_are_chosen_by_their_fields() {
+        let entry: PatchEntry = serde_json::from_value(serde_json::json!({
+            "name": "Force 1.2sec cutscenes",
+            "description": "",
+            "alt_author": null,
+            "patches": [
+                { "offset": "0x4d5ac", "offset_type": "Virtual", "call_to": "0x14c01" },
+                { "offset": "0x4d5b1", "offset_type": "Virtual", "fill": "0x90", "length": 3 },
+                { "offset": "0x4d5b4", "offset_type": "Virtual", "copy_from": "0x14c01", "length": 9 }
+            ]
+        }))
+        .unwrap();
+
+        let patches = entry.get_patches();
+
+        assert_eq!(
+            patches[0].contents,
+            PatchContents::Call { call_to: 0x14c01 }
+        );
+        assert_eq!(
+            patches[1].contents.fixed_bytes(),
+            Some(vec![0x90, 0x90, 0x90])
+        );
+        assert_eq!(entry.patch_len(&patches[2]), Some(9));
+    }
+}
@@ -0,0 +1,80 @@
+const JMP_REL32: u8 = 0xE9;
+const CALL_REL32: u8 = 0xE8;
+
+/// The number of bytes written by a jump or call patch.
+pub const BRANCH_LEN: usize = 5;
+
+/// The most bytes a fill or copy patch can write. This is far more than any real patch needs, and
+/// stops a mistyped length from using up all of the computer's memory.
+pub const MAX_LENGTH: usize = 0x100000;
+
+/// Repeats a pattern of bytes until it fills `length` bytes, such as a run of nops. Fails if
+/// nothing would be written, or more than MAX_LENGTH bytes.
+pub fn fill(pattern: &[u8], length: usize) -> Result<Vec<u8>, String> {
+    if pattern.is_empty() {
+        return Err("the pattern to fill with is empty".to_string());
+    }
+
+    if length == 0 {
+        return Err("the patch writes no bytes".to_string());
+    }
+
+    if length > MAX_LENGTH {
+        return Err(format!(
+            "the patch writes {} bytes, but can write at most {}",
+            length, MAX_LENGTH
+        ));
+    }
+
+    Ok(pattern.iter().copied().cycle().take(length).collect())
+}
+
+/// A relative jump written at address `from`, which lands on address `to`.
+pub fn jump(from: u32, to: u32) -> Vec<u8> {
+    branch(JMP_REL32, from, to)
+}
+
+/// A relative call written at address `from`, which calls the function at address `to`.
+pub fn call(from: u32, to: u32) -> Vec<u8> {
+    branch(CALL_REL32, from, to)
+}
+
+/// An absolute pointer to an address, as stored in memory.
+pub fn pointer(to: u32) -> Vec<u8> {
+    to.to_le_bytes().to_vec()
+}
+
+// The displacement is counted from the end of the instruction, and wraps like the CPU does
+fn branch(opcode: u8, from: u32, to: u32) -> Vec<u8> {
+    let displacement = to.wrapping_sub(from.wrapping_add(BRANCH_LEN as u32));
+
+    let mut bytes = vec![opcode];
+    bytes.extend(displacement.to_le_bytes());
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::patching::operation::{MAX_LENGTH, call, fill, jump, pointer};
+
+    #[test]
+    fn fills_with_repeated_patterns() {
+        assert_eq!(fill(&[0x90], 3).unwrap(), vec![0x90, 0x90, 0x90]);
+        assert_eq!(fill(&[0x66, 0x90], 3).unwrap(), vec![0x66, 0x90, 0x66]);
+    }
+
+    #[test]
+    fn fills_must_write_something() {
+        assert!(fill(&[], 3).is_err());
+        assert!(fill(&[0x90], 0).is_err());
+        assert!(fill(&[0x90], MAX_LENGTH + 1).is_err());
+    }
+
+    #[test]
+    fn branches_are_relative_to_the_next_instruction() {
+        assert_eq!(jump(0x4d5ac, 0x4d5b1), vec![0xe9, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(call(0x4d5ac, 0x14c01), vec![0xe8, 0x50, 0x76, 0xfc, 0xff]);
+        assert_eq!(pointer(0x14c01), vec![0x01, 0x4c, 0x01, 0x00]);
+    }
+}
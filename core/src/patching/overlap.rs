@@ -0,0 +1,214 @@
+use std::fmt::Display;
+
+use crate::{
+    HasPatches,
+    patching::{Patch, PatchEntry, ResolvedPatch},
+};
+
+/// Two entries that write to the same bytes of a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchOverlap {
+    pub first_entry: String,
+    pub second_entry: String,
+
+    // The file both entries write to, or None for default.xbe
+    pub file: Option<String>,
+
+    // Start of the overlapping bytes in the file
+    pub file_offset: u64,
+
+    // The bytes each entry writes into the overlapping range
+    pub first_bytes: Vec<u8>,
+    pub second_bytes: Vec<u8>,
+}
+
+impl Display for PatchOverlap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        };
+
+        write!(
+            f,
+            "\"{}\" and \"{}\" both write {} byte(s) at file offset 0x{:08X} of {} (0x{} and 0x{})",
+            self.first_entry,
+            self.second_entry,
+            self.first_bytes.len(),
+            self.file_offset,
+            self.file.as_deref().unwrap_or("default.xbe"),
+            hex(&self.first_bytes),
+            hex(&self.second_bytes)
+        )
+    }
+}
+
+struct PatchRange {
+    entry: usize,
+    file: Option<String>,
+    start: u64,
+    bytes: Vec<u8>,
+}
+
+impl PatchRange {
+    fn end(&self) -> u64 {
+        self.start + self.bytes.len() as u64
+    }
+}
+
+/// Finds every pair of entries whose patches write to overlapping bytes. `resolve_patch` works out
+/// where each patch will be written and what it writes.
+pub fn find_overlaps<F>(
+    entries: &[PatchEntry],
+    resolve_patch: F,
+) -> Result<Vec<PatchOverlap>, std::io::Error>
+where
+    F: Fn(&Patch) -> Result<ResolvedPatch, std::io::Error>,
+{
+    let mut ranges = Vec::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        for patch in entry.get_patches() {
+            let resolved = resolve_patch(patch)?;
+
+            ranges.push(PatchRange {
+                entry: i,
+                file: resolved.file,
+                start: resolved.file_offset,
+                bytes: resolved.bytes,
+            });
+        }
+    }
+
+    ranges.sort_by(|a, b| (&a.file, a.start).cmp(&(&b.file, b.start)));
+
+    let mut overlaps = Vec::new();
+
+    for (i, first) in ranges.iter().enumerate() {
+        for second in ranges[i + 1..]
+            .iter()
+            .take_while(|r| r.file == first.file && r.start < first.end())
+        {
+            if first.entry == second.entry {
+                continue;
+            }
+
+            let start = second.start;
+            let end = first.end().min(second.end());
+
+            let first_slice = (start - first.start) as usize..(end - first.start) as usize;
+            let second_slice = 0..(end - start) as usize;
+
+            // Keep the entries in the order they were given
+            let (a, a_slice, b, b_slice) = if first.entry < second.entry {
+                (first, first_slice, second, second_slice)
+            } else {
+                (second, second_slice, first, first_slice)
+            };
+
+            overlaps.push(PatchOverlap {
+                first_entry: entries[a.entry].name().clone(),
+                second_entry: entries[b.entry].name().clone(),
+                file: first.file.clone(),
+                file_offset: start,
+                first_bytes: a.bytes[a_slice].to_vec(),
+                second_bytes: b.bytes[b_slice].to_vec(),
+            });
+        }
+    }
+
+    Ok(overlaps)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::patching::{
+        Patch, PatchContents, PatchEntry, PatchOffsetType, ResolvedPatch, overlap::find_overlaps,
+    };
+
+    fn raw_entry(name: &str, offset: u32, bytes: Vec<u8>) -> PatchEntry {
+        PatchEntry::new(
+            name.to_string(),
+            String::new(),
+            None,
+            vec![Patch {
+                offset,
+                offset_type: PatchOffsetType::Raw,
+                file: None,
+                contents: PatchContents::Bytes {
+                    replacement_bytes: bytes,
+                },
+                original_bytes: None,
+            }],
+        )
+    }
+
+    fn raw_offset(patch: &Patch) -> Result<ResolvedPatch, std::io::Error> {
+        let PatchContents::Bytes { replacement_bytes } = &patch.contents else {
+            panic!("Only byte patches are used in these tests");
+        };
+
+        Ok(ResolvedPatch {
+            file: patch.file.clone(),
+            file_offset: patch.offset.into(),
+            bytes: replacement_bytes.clone(),
+        })
+    }
+
+    #[test]
+    fn same_address_overlaps() {
+        let entries = vec![
+            raw_entry("4:3", 0x5077f8, vec![0x00]),
+            raw_entry("16:9", 0x5077f8, vec![0x01]),
+            raw_entry("2.35:1", 0x5077f8, vec![0x02]),
+        ];
+
+        let overlaps = find_overlaps(&entries, raw_offset).unwrap();
+
+        assert_eq!(overlaps.len(), 3);
+        assert_eq!(overlaps[0].first_entry, "4:3");
+        assert_eq!(overlaps[0].second_entry, "16:9");
+        assert_eq!(overlaps[0].file_offset, 0x5077f8);
+        assert_eq!(overlaps[0].first_bytes, vec![0x00]);
+        assert_eq!(overlaps[0].second_bytes, vec![0x01]);
+    }
+
+    #[test]
+    fn partial_overlap_reports_shared_bytes() {
+        let entries = vec![
+            raw_entry("second", 0x1003, vec![0xaa, 0xbb, 0xcc]),
+            raw_entry("first", 0x1000, vec![0x90, 0x90, 0x90, 0x90, 0x90]),
+            raw_entry("separate", 0x1005, vec![0xeb]),
+        ];
+
+        let overlaps = find_overlaps(&entries, raw_offset).unwrap();
+
+        assert_eq!(overlaps.len(), 2);
+
+        assert_eq!(overlaps[0].first_entry, "second");
+        assert_eq!(overlaps[0].second_entry, "first");
+        assert_eq!(overlaps[0].file_offset, 0x1003);
+        assert_eq!(overlaps[0].first_bytes, vec![0xaa, 0xbb]);
+        assert_eq!(overlaps[0].second_bytes, vec![0x90, 0x90]);
+
+        assert_eq!(overlaps[1].first_entry, "second");
+        assert_eq!(overlaps[1].second_entry, "separate");
+        assert_eq!(overlaps[1].file_offset, 0x1005);
+        assert_eq!(overlaps[1].first_bytes, vec![0xcc]);
+        assert_eq!(overlaps[1].second_bytes, vec![0xeb]);
+    }
+
+    #[test]
+    fn different_files_dont_overlap() {
+        let mut config = raw_entry("config", 0x10, vec![0x31]);
+        config
+            .all_patches_mut()
+            .for_each(|p| p.file = Some("media/config.ini".to_string()));
+
+        let entries = vec![raw_entry("xbe", 0x10, vec![0x90]), config];
+
+        assert!(find_overlaps(&entries, raw_offset).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The type a parameter's value is written as. All values are little endian.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+pub enum ParameterType {
+    U8,
+    U16,
+    U32,
+    I32,
+    F32,
+    F64,
+}
+
+impl ParameterType {
+    pub fn size(&self) -> usize {
+        match self {
+            ParameterType::U8 => 1,
+            ParameterType::U16 => 2,
+            ParameterType::U32 | ParameterType::I32 | ParameterType::F32 => 4,
+            ParameterType::F64 => 8,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        !matches!(self, ParameterType::F32 | ParameterType::F64)
+    }
+
+    pub fn encode(&self, value: f64) -> Result<Vec<u8>, String> {
+        if !value.is_finite() {
+            return Err(format!("{} is not a valid number", value));
+        }
+
+        if self.is_integer() {
+            let (min, max) = match self {
+                ParameterType::U8 => (u8::MIN as f64, u8::MAX as f64),
+                ParameterType::U16 => (u16::MIN as f64, u16::MAX as f64),
+                ParameterType::U32 => (u32::MIN as f64, u32::MAX as f64),
+                _ => (i32::MIN as f64, i32::MAX as f64),
+            };
+
+            if value.fract() != 0.0 {
+                return Err(format!("{} is not a whole number", value));
+            }
+
+            if value < min || value > max {
+                return Err(format!(
+                    "{} doesn't fit in a {:?} ({} to {})",
+                    value, self, min, max
+                ));
+            }
+        }
+
+        Ok(match self {
+            ParameterType::U8 => vec![value as u8],
+            ParameterType::U16 => (value as u16).to_le_bytes().to_vec(),
+            ParameterType::U32 => (value as u32).to_le_bytes().to_vec(),
+            ParameterType::I32 => (value as i32).to_le_bytes().to_vec(),
+            ParameterType::F32 => {
+                let single = value as f32;
+
+                // Casting rounds values too large for an f32 to infinity
+                if !single.is_finite() {
+                    return Err(format!("{} doesn't fit in a {:?}", value, self));
+                }
+
+                single.to_le_bytes().to_vec()
+            }
+            ParameterType::F64 => value.to_le_bytes().to_vec(),
+        })
+    }
+}
+
+/// A value chosen by the user when enabling an entry, which patches in the entry can write by
+/// naming it in their `parameter` field.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct EntryParameter {
+    pub name: String,
+
+    #[serde(default)]
+    pub description: String,
+
+    pub value_type: ParameterType,
+    pub default: f64,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+
+    // Shown next to the value, eg. "x" or "fps"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+}
+
+impl EntryParameter {
+    /// Checks a value against the parameter's bounds and type, and encodes it.
+    pub fn encode(&self, value: f64) -> Result<Vec<u8>, String> {
+        let unit = self.unit.as_deref().unwrap_or_default();
+
+        if let Some(min) = self.min
+            && value < min
+        {
+            return Err(format!(
+                "{}{} is less than the minimum of {}{}",
+                value, unit, min, unit
+            ));
+        }
+
+        if let Some(max) = self.max
+            && value > max
+        {
+            return Err(format!(
+                "{}{} is more than the maximum of {}{}",
+                value, unit, max, unit
+            ));
+        }
+
+        self.value_type.encode(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::patching::parameter::{EntryParameter, ParameterType};
+
+    #[test]
+    fn encodes_little_endian() {
+        assert_eq!(ParameterType::U8.encode(60.0).unwrap(), vec![0x3c]);
+        assert_eq!(
+            ParameterType::U16.encode(0x1234 as f64).unwrap(),
+            vec![0x34, 0x12]
+        );
+        assert_eq!(
+            ParameterType::I32.encode(-2.0).unwrap(),
+            vec![0xfe, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(
+            ParameterType::F32.encode(2.5).unwrap(),
+            vec![0x00, 0x00, 0x20, 0x40]
+        );
+        assert_eq!(
+            ParameterType::F64.encode(1.0).unwrap(),
+            vec![0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f]
+        );
+    }
+
+    #[test]
+    fn rejects_values_that_dont_fit() {
+        assert!(ParameterType::U8.encode(256.0).is_err());
+        assert!(ParameterType::U32.encode(-1.0).is_err());
+        assert!(ParameterType::U16.encode(1.5).is_err());
+        assert!(ParameterType::F32.encode(f64::NAN).is_err());
+        assert!(ParameterType::F32.encode(1e300).is_err());
+        assert!(ParameterType::F64.encode(1e300).is_ok());
+
+        let speed = EntryParameter {
+            name: "Speed".to_string(),
+            description: String::new(),
+            value_type: ParameterType::F32,
+            default: 1.0,
+            min: Some(0.5),
+            max: Some(4.0),
+            unit: Some("x".to_string()),
+        };
+
+        assert!(speed.encode(4.0).is_ok());
+        assert_eq!(
+            speed.encode(5.0).unwrap_err(),
+            "5x is more than the maximum of 4x"
+        );
+    }
+}
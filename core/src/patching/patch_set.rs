@@ -0,0 +1,316 @@
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    file_handling::LiveFile,
+    patching::{
+        Patch, PatchEntry, PatchOffsetType,
+        include::{self, PatchSetInclude},
+        schema::{self, se_current_schema},
+        symbol::Symbol,
+        target::{self, PatchTarget, TargetMatch},
+        translation::{self, Translations},
+    },
+    xbe::{XBEIdentity, XBEReader},
+};
+
+// Serialized through the impls below, so that older schema versions are migrated when read
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
+#[serde(remote = "Self")]
+pub struct PatchSet {
+    // Where editors can find the JSON Schema for patch set files
+    #[serde(rename = "$schema", default, skip_serializing_if = "Option::is_none")]
+    pub json_schema: Option<String>,
+
+    #[serde(serialize_with = "se_current_schema")]
+    pub xbpatchset_schema: u32,
+    pub name: String,
+
+    // The name in other languages, keyed by language code, eg. "ja". Only the name of a patch set
+    // is translated, so descriptions here are ignored.
+    #[serde(default, skip_serializing_if = "Translations::is_empty")]
+    pub translations: Translations,
+
+    pub author: String,
+    pub version_major: u8,
+    pub version_minor: u8,
+    pub game_title: String,
+
+    // Other patch sets whose entries are added to this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<PatchSetInclude>,
+
+    // The builds of the game this patch set is known to work with
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<PatchTarget>,
+    pub entries: Vec<PatchEntry>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub option_groups: Vec<OptionGroup>,
+
+    // The order entry categories are listed in. Categories that aren't listed follow in the order
+    // they're first used.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub symbols: Vec<Symbol>,
+
+    // The folder the patch set was loaded from, which the sources of file changes are relative to
+    #[serde(skip)]
+    pub folder: Option<PathBuf>,
+}
+
+impl Serialize for PatchSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        PatchSet::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PatchSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = schema::migrate(serde_json::Value::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)?;
+
+        PatchSet::deserialize(value).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Default, Debug, PartialEq)]
+pub enum OptionGroupSelection {
+    #[default]
+    AtMostOne,
+    ExactlyOne,
+}
+
+/// A single setting made up of several alternative entries. Entries join a group by naming it in
+/// their `group` field.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
+pub struct OptionGroup {
+    pub name: String,
+
+    #[serde(default)]
+    pub description: String,
+
+    #[serde(default)]
+    pub selection: OptionGroupSelection,
+}
+
+impl PatchSet {
+    pub fn from_file(path: &PathBuf) -> Result<PatchSet, std::io::Error> {
+        let file = File::open(path)?;
+        let mut patch_set: PatchSet = serde_json::from_reader(file)?;
+
+        patch_set.folder = path.parent().map(Path::to_path_buf);
+        Ok(patch_set)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The patch set's name in a language, or its untranslated name if there is no translation.
+    pub fn localised_name(&self, language: Option<&str>) -> &str {
+        translation::localise(
+            &self.translations,
+            language,
+            |t| t.name.as_ref(),
+            &self.name,
+        )
+    }
+
+    /// Every language the patch set or its entries have been translated into.
+    pub fn languages(&self) -> BTreeSet<&String> {
+        self.translations
+            .keys()
+            .chain(self.entries.iter().flat_map(|e| e.translations().keys()))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Gets the definition of an option group. Groups that are referenced by entries but never
+    /// defined behave like an `AtMostOne` group with no description.
+    pub fn option_group(&self, name: &str) -> OptionGroup {
+        self.option_groups
+            .iter()
+            .find(|g| g.name == name)
+            .cloned()
+            .unwrap_or_else(|| OptionGroup {
+                name: name.to_string(),
+                ..Default::default()
+            })
+    }
+
+    /// Checks whether an XBE is one of the builds this patch set was made for.
+    pub fn check_targets(&self, xbe: &XBEIdentity) -> TargetMatch {
+        target::check_targets(&self.name, &self.targets, xbe)
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| s.name == name)
+    }
+
+    /// Copies a patch, replacing a reference to one of this patch set's symbols with the symbol
+    /// itself, so that it can be placed without the rest of the patch set.
+    pub fn resolve_symbol(&self, patch: &Patch) -> Result<Patch, String> {
+        resolve_symbol(&self.symbols, patch)
+    }
+
+    /// Reads the bytes currently at each patch's location in a clean XBE, and stores them as the
+    /// patch's original bytes. Only the patches used on the XBE's build are filled in, so entries
+    /// with variants need a clean XBE of each build, and patches to other files in the game are
+    /// skipped. Symbols are looked up in `symbols`, which should include those of any included
+    /// patch sets. Returns the number of patches that were filled in.
+    pub fn fill_original_bytes(
+        &mut self,
+        reader: &mut XBEReader,
+        symbols: &[Symbol],
+    ) -> Result<usize, std::io::Error> {
+        let identity = reader.identify()?;
+
+        if let TargetMatch::WrongGame(reason) = self.check_targets(&identity) {
+            return Err(std::io::Error::other(format!("{}.", reason)));
+        }
+
+        let mut filled = 0;
+
+        for e in 0..self.entries.len() {
+            let entry = &self.entries[e];
+            let mut originals = Vec::new();
+
+            for (i, patch) in entry.patches_for(&identity).iter().enumerate() {
+                if patch.file.is_some() {
+                    originals.push(None);
+                    continue;
+                }
+
+                let len = entry.patch_len(patch).ok_or(std::io::Error::other(format!(
+                    "Unable to tell how many bytes patch {} of \"{}\" writes.",
+                    i,
+                    entry.name()
+                )))?;
+
+                let bytes = resolve_symbol(symbols, patch)
+                    .map_err(std::io::Error::other)
+                    .and_then(|p| reader.read_original_bytes(&p, len))
+                    .map_err(|err| {
+                        std::io::Error::other(format!(
+                            "Unable to read original bytes for patch {} of \"{}\". Error: {}",
+                            i,
+                            entry.name(),
+                            err
+                        ))
+                    })?;
+
+                originals.push(Some(bytes));
+            }
+
+            for (patch, bytes) in self.entries[e]
+                .patches_for_mut(&identity)
+                .iter_mut()
+                .zip(originals)
+            {
+                if let Some(bytes) = bytes {
+                    patch.original_bytes = Some(bytes);
+                    filled += 1;
+                }
+            }
+        }
+
+        Ok(filled)
+    }
+}
+
+fn resolve_symbol(symbols: &[Symbol], patch: &Patch) -> Result<Patch, String> {
+    let mut patch = patch.clone();
+
+    if let PatchOffsetType::Symbol(name) = &patch.offset_type {
+        let symbol = symbols
+            .iter()
+            .find(|s| s.name == *name)
+            .ok_or(format!("the patch set has no symbol named \"{}\"", name))?;
+
+        patch.offset_type = PatchOffsetType::ResolvedSymbol(symbol.clone());
+    }
+
+    Ok(patch)
+}
+
+/// Fills in the original bytes of a patch set file using a clean XBE, then saves the file. Nothing
+/// is written if any patch can't be read. Patches can use the symbols of included patch sets, but
+/// only the file's own entries are filled in.
+pub fn fill_original_bytes(
+    file: &mut LiveFile<PatchSet>,
+    xbe_path: &PathBuf,
+) -> Result<usize, std::io::Error> {
+    let mut reader = XBEReader::new(xbe_path)?;
+
+    let mut patch_set = file.data().clone();
+    let symbols = include::resolve_includes(patch_set.clone(), file.path())?.symbols;
+    let filled = patch_set.fill_original_bytes(&mut reader, &symbols)?;
+
+    file.update(|ps| *ps = patch_set)?;
+
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        HasPatches,
+        file_handling::LiveFile,
+        patching::patch_set::{PatchSet, fill_original_bytes},
+        test_util::{entry, patch_set_json, temp_folder, test_xbe},
+    };
+
+    #[test]
+    fn original_bytes_can_use_included_symbols() {
+        let folder = temp_folder("original_bytes_can_use_included_symbols");
+
+        let mut base = patch_set_json("engine base", serde_json::json!([]));
+        base["symbols"] = serde_json::json!([{ "name": "frame_cap", "address": "0x11004" }]);
+        std::fs::write(folder.join("base.json"), base.to_string()).unwrap();
+
+        let mut ghoulies = patch_set_json(
+            "ghoulies",
+            serde_json::json!([entry(
+                "Uncap frame rate",
+                serde_json::json!({
+                    "patches": [
+                        { "offset": "0x2", "offset_type": { "Symbol": "frame_cap" }, "replacement_bytes": "0x9090" }
+                    ]
+                })
+            )]),
+        );
+        ghoulies["includes"] = serde_json::json!([{ "path": "base.json" }]);
+
+        let path = folder.join("ghoulies.json");
+        std::fs::write(&path, ghoulies.to_string()).unwrap();
+
+        let xbe_path = folder.join("default.xbe");
+        let code: Vec<u8> = (0..0x10).collect();
+        std::fs::write(&xbe_path, test_xbe(&[(".text", 0x11000, &code)])).unwrap();
+
+        let mut file = LiveFile::<PatchSet>::from_existing(&path).unwrap();
+        assert_eq!(fill_original_bytes(&mut file, &xbe_path).unwrap(), 1);
+
+        // Only the patch set's own file is written, without the included symbols
+        let saved = PatchSet::from_file(&path).unwrap();
+        assert_eq!(
+            saved.entries[0].get_patches()[0].original_bytes,
+            Some(vec![0x06, 0x07])
+        );
+        assert!(saved.symbols.is_empty());
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::patching::{
+    parameter::ParameterType,
+    serialization::{de_hex_list_to_vvu8, hex_bytes_list_schema, se_vvu8_to_hex},
+};
+
+/// The values a random patch picks between. Which one is picked depends only on the job's seed
+/// and where the patch is, so the same seed always gives the same build.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum RandomValues {
+    // One of a list of byte strings, eg. ["0x01", "0x04", "0x07"] for a few scene IDs
+    Choices {
+        #[serde(
+            serialize_with = "se_vvu8_to_hex",
+            deserialize_with = "de_hex_list_to_vvu8"
+        )]
+        #[schemars(schema_with = "hex_bytes_list_schema")]
+        choices: Vec<Vec<u8>>,
+    },
+    // A number from min to max, including both, written as value_type. Integer types only pick
+    // whole numbers.
+    Range {
+        min: f64,
+        max: f64,
+        value_type: ParameterType,
+    },
+}
+
+impl RandomValues {
+    /// The number of bytes every value takes up, if they're all the same length.
+    pub fn size(&self) -> Option<usize> {
+        match self {
+            RandomValues::Choices { choices } => {
+                let len = choices.first()?.len();
+                choices.iter().all(|c| c.len() == len).then_some(len)
+            }
+            RandomValues::Range { value_type, .. } => Some(value_type.size()),
+        }
+    }
+
+    /// Checks that there is at least one value to pick, and that every value can be written.
+    pub fn check(&self) -> Result<(), String> {
+        match self {
+            RandomValues::Choices { choices } => {
+                if choices.is_empty() {
+                    return Err("there are no values to choose from".to_string());
+                }
+
+                if choices.iter().any(Vec::is_empty) {
+                    return Err("one of the values to choose from is empty".to_string());
+                }
+
+                Ok(())
+            }
+            RandomValues::Range {
+                min,
+                max,
+                value_type,
+            } => {
+                if min > max {
+                    return Err(format!(
+                        "the minimum {} is more than the maximum {}",
+                        min, max
+                    ));
+                }
+
+                value_type.encode(*min)?;
+                value_type.encode(*max)?;
+
+                if value_type.is_integer() && min.ceil() > max.floor() {
+                    return Err(format!(
+                        "there are no whole numbers from {} to {}",
+                        min, max
+                    ));
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Picks a value and encodes it.
+    pub fn pick(&self, rng: &mut SeededRng) -> Result<Vec<u8>, String> {
+        self.check()?;
+
+        match self {
+            RandomValues::Choices { choices } => {
+                Ok(choices[rng.below(choices.len() as u64) as usize].clone())
+            }
+            RandomValues::Range {
+                min,
+                max,
+                value_type,
+            } => {
+                let value = if value_type.is_integer() {
+                    let (min, max) = (min.ceil(), max.floor());
+                    min + rng.below((max - min) as u64 + 1) as f64
+                } else {
+                    min + (max - min) * rng.unit()
+                };
+
+                value_type.encode(value)
+            }
+        }
+    }
+}
+
+/// A new seed for a job that wasn't given one.
+pub fn new_seed() -> u64 {
+    // The clock is random enough when the system can't provide anything better
+    getrandom::u64().unwrap_or_else(|_| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
+    })
+}
+
+/// A small SplitMix64 generator. It is written out here rather than taken from a crate so that a
+/// seed keeps giving the same values in every version of xbpatch.
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    /// A generator for one patch. Each patch gets its own, so enabling or disabling other entries
+    /// doesn't change the values it picks.
+    pub fn for_patch(seed: u64, patch_set: &str, entry: &str, patch: usize) -> SeededRng {
+        let mut hasher = Sha256::new();
+
+        hasher.update(seed.to_le_bytes());
+        hasher.update(patch_set.as_bytes());
+        hasher.update([0]);
+        hasher.update(entry.as_bytes());
+        hasher.update([0]);
+        hasher.update((patch as u64).to_le_bytes());
+
+        let digest = hasher.finalize();
+
+        SeededRng {
+            state: u64::from_le_bytes(digest[..8].try_into().unwrap()),
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number from 0 up to but not including n.
+    pub fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+
+    /// A number from 0 up to but not including 1.
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::patching::{
+        parameter::ParameterType,
+        random::{RandomValues, SeededRng},
+    };
+
+    #[test]
+    fn same_seed_picks_same_values() {
+        let scenes = RandomValues::Choices {
+            choices: (0..20).map(|i| vec![i]).collect(),
+        };
+
+        let picks = |seed| -> Vec<Vec<u8>> {
+            (0..8)
+                .map(|i| {
+                    let mut rng = SeededRng::for_patch(seed, "ghoulies", "Random scene", i);
+                    scenes.pick(&mut rng).unwrap()
+                })
+                .collect()
+        };
+
+        assert_eq!(picks(1234), picks(1234));
+        assert_ne!(picks(1234), picks(1235));
+
+        // The values for a seed must never change between versions
+        assert_eq!(SeededRng { state: 0 }.next_u64(), 0xE220A8397B1DCDAF);
+    }
+
+    #[test]
+    fn ranges_stay_in_bounds() {
+        let range = RandomValues::Range {
+            min: -2.0,
+            max: 3.0,
+            value_type: ParameterType::I32,
+        };
+
+        for i in 0..200 {
+            let mut rng = SeededRng::for_patch(99, "ghoulies", "Random gravity", i);
+            let bytes = range.pick(&mut rng).unwrap();
+            let value = i32::from_le_bytes(bytes.try_into().unwrap());
+
+            assert!((-2..=3).contains(&value));
+        }
+
+        let backwards = RandomValues::Range {
+            min: 5.0,
+            max: 1.0,
+            value_type: ParameterType::U8,
+        };
+        assert!(backwards.check().is_err());
+
+        let too_big = RandomValues::Range {
+            min: 0.0,
+            max: 300.0,
+            value_type: ParameterType::U8,
+        };
+        assert!(too_big.check().is_err());
+
+        let empty = RandomValues::Choices { choices: vec![] };
+        assert!(empty.check().is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use serde::Serializer;
+use serde_json::Value;
+
+use crate::patching::PatchSet;
+
+/// The newest version of the patch set schema. Patch sets are always written in this version.
+pub const CURRENT_SCHEMA: u32 = 1;
+
+/// The name the JSON Schema is shipped under, next to the patch sets that refer to it.
+pub const JSON_SCHEMA_FILE_NAME: &str = "xbpatchset.schema.json";
+
+// Upgrades a patch set from one schema version to the next
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+// Each migration upgrades a patch set from the schema version at its index to the next version
+const MIGRATIONS: [Migration; CURRENT_SCHEMA as usize] = [
+    // Version 1 only added optional fields, so version 0 patch sets can be read as they are
+    |_| Ok(()),
+];
+
+/// Upgrades a patch set written in any known version of the schema to the current version.
+pub fn migrate(mut patch_set: Value) -> Result<Value, String> {
+    let version = patch_set
+        .get("xbpatchset_schema")
+        .ok_or("The patch set has no xbpatchset_schema.".to_string())?
+        .as_u64()
+        .ok_or("xbpatchset_schema must be a whole number.".to_string())?;
+
+    if version > CURRENT_SCHEMA as u64 {
+        return Err(format!(
+            "The patch set uses schema version {}, but this version of XBPatch only supports up to version {}. Try updating XBPatch.",
+            version, CURRENT_SCHEMA
+        ));
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(&mut patch_set).map_err(|e| {
+            format!(
+                "Unable to upgrade the patch set from schema version {} to {}. {}",
+                from,
+                from + 1,
+                e
+            )
+        })?;
+    }
+
+    patch_set["xbpatchset_schema"] = CURRENT_SCHEMA.into();
+
+    Ok(patch_set)
+}
+
+/// Generates a JSON Schema describing patch set files, for editors to validate and autocomplete
+/// them with.
+pub fn json_schema() -> Value {
+    let mut schema = schemars::schema_for!(PatchSet).to_value();
+    schema["title"] = "XBPatch patch set".into();
+
+    schema
+}
+
+pub fn se_current_schema<S>(_: &u32, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_u32(CURRENT_SCHEMA)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::patching::{
+        PatchSet,
+        schema::{CURRENT_SCHEMA, json_schema, migrate},
+    };
+
+    #[test]
+    fn old_versions_are_upgraded() {
+        let patch_set: PatchSet = serde_json::from_value(serde_json::json!({
+            "xbpatchset_schema": 0,
+            "name": "ghoulies",
+            "author": "hethan/rectangle",
+            "version_major": 1,
+            "version_minor": 0,
+            "game_title": "Grabbed by the Ghoulies",
+            "entries": []
+        }))
+        .unwrap();
+
+        assert_eq!(patch_set.xbpatchset_schema, CURRENT_SCHEMA);
+
+        let written = serde_json::to_value(PatchSet::default()).unwrap();
+        assert_eq!(written["xbpatchset_schema"], CURRENT_SCHEMA);
+    }
+
+    #[test]
+    fn shipped_json_schema_is_up_to_date() {
+        let shipped: serde_json::Value =
+            serde_json::from_str(include_str!("../../../example/xbpatchset.schema.json")).unwrap();
+
+        assert!(
+            shipped == json_schema(),
+            "example/xbpatchset.schema.json is out of date. Regenerate it with xbpatch-cli --write-json-schema"
+        );
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        assert!(migrate(serde_json::json!({ "xbpatchset_schema": CURRENT_SCHEMA + 1 })).is_err());
+        assert!(migrate(serde_json::json!({ "name": "ghoulies" })).is_err());
+    }
+}
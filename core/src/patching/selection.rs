@@ -0,0 +1,402 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use crate::patching::{
+    PatchSet,
+    parameter::EntryParameter,
+    patch_set::{OptionGroup, OptionGroupSelection},
+};
+
+/// Which entries of a patch set are enabled, and the values chosen for their parameters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SetSelection {
+    pub enabled: Vec<bool>,
+
+    // Keyed by entry index and parameter name. Parameters without a value use their default.
+    pub values: BTreeMap<(usize, String), f64>,
+}
+
+impl SetSelection {
+    pub fn new(patch_set: &PatchSet) -> Self {
+        SetSelection {
+            enabled: vec![false; patch_set.len()],
+            values: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self, entry: usize) -> bool {
+        self.enabled.get(entry).cloned().unwrap_or(false)
+    }
+
+    pub fn value(&self, entry: usize, parameter: &EntryParameter) -> f64 {
+        self.values
+            .get(&(entry, parameter.name.clone()))
+            .cloned()
+            .unwrap_or(parameter.default)
+    }
+
+    pub fn set_value(&mut self, entry: usize, parameter: &str, value: f64) {
+        self.values.insert((entry, parameter.to_string()), value);
+    }
+}
+
+/// A single choice shown to the user when picking entries. All of the entries in an option group
+/// are shown together as one choice, at the position of the group's first entry.
+#[derive(Debug, Clone)]
+pub enum SelectionItem {
+    Entry(usize),
+    Group {
+        group: OptionGroup,
+        options: Vec<usize>,
+    },
+}
+
+pub fn selection_items(patch_set: &PatchSet) -> Vec<SelectionItem> {
+    let mut items: Vec<SelectionItem> = Vec::new();
+
+    for (i, entry) in patch_set.entries.iter().enumerate() {
+        let Some(group_name) = entry.group() else {
+            items.push(SelectionItem::Entry(i));
+            continue;
+        };
+
+        let existing = items.iter_mut().find_map(|item| match item {
+            SelectionItem::Group { group, options } if group.name == group_name => Some(options),
+            _ => None,
+        });
+
+        match existing {
+            Some(options) => options.push(i),
+            None => items.push(SelectionItem::Group {
+                group: patch_set.option_group(group_name),
+                options: vec![i],
+            }),
+        }
+    }
+
+    items
+}
+
+impl SelectionItem {
+    /// The entries chosen between by this item.
+    pub fn entries(&self) -> &[usize] {
+        match self {
+            SelectionItem::Entry(i) => std::slice::from_ref(i),
+            SelectionItem::Group { options, .. } => options,
+        }
+    }
+}
+
+/// The selection items listed under one heading.
+#[derive(Debug, Clone)]
+pub struct SelectionCategory {
+    // None for the entries that don't have a category
+    pub name: Option<String>,
+    pub items: Vec<SelectionItem>,
+}
+
+/// Sorts the selection items under their categories, which are listed in the patch set's order
+/// followed by the entries without a category. An option group is listed under the category of its
+/// first entry.
+pub fn categorised_selection_items(patch_set: &PatchSet) -> Vec<SelectionCategory> {
+    let mut categories: Vec<SelectionCategory> = patch_set
+        .categories
+        .iter()
+        .map(|name| SelectionCategory {
+            name: Some(name.clone()),
+            items: Vec::new(),
+        })
+        .collect();
+
+    let mut uncategorised = Vec::new();
+
+    for item in selection_items(patch_set) {
+        let Some(name) = patch_set.entries[item.entries()[0]].category() else {
+            uncategorised.push(item);
+            continue;
+        };
+
+        match categories
+            .iter_mut()
+            .find(|c| c.name.as_deref() == Some(name))
+        {
+            Some(category) => category.items.push(item),
+            None => categories.push(SelectionCategory {
+                name: Some(name.to_string()),
+                items: vec![item],
+            }),
+        }
+    }
+
+    categories.push(SelectionCategory {
+        name: None,
+        items: uncategorised,
+    });
+
+    categories.retain(|c| !c.items.is_empty());
+
+    for category in &mut categories {
+        category.items.sort_by_key(|item| {
+            let order = item
+                .entries()
+                .iter()
+                .filter_map(|i| patch_set.entries[*i].order())
+                .min();
+
+            (order.is_none(), order)
+        });
+    }
+
+    categories
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectionError {
+    MultipleOptions {
+        patch_set: String,
+        group: String,
+        options: Vec<String>,
+    },
+    NoOption {
+        patch_set: String,
+        group: String,
+    },
+    MissingRequirement {
+        entry: String,
+        requirement: String,
+    },
+    UnknownRequirement {
+        entry: String,
+        requirement: String,
+    },
+    Conflict {
+        entry: String,
+        conflicts_with: String,
+    },
+    InvalidParameter {
+        entry: String,
+        parameter: String,
+        reason: String,
+    },
+    UnknownSymbol {
+        entry: String,
+        reason: String,
+    },
+    InvalidRandom {
+        entry: String,
+        reason: String,
+    },
+}
+
+impl Display for SelectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SelectionError::MultipleOptions {
+                patch_set,
+                group,
+                options,
+            } => write!(
+                f,
+                "Only one option can be chosen for \"{}\" in {}, but {} were chosen: {}",
+                group,
+                patch_set,
+                options.len(),
+                options.join(", ")
+            ),
+            SelectionError::NoOption { patch_set, group } => write!(
+                f,
+                "An option must be chosen for \"{}\" in {}",
+                group, patch_set
+            ),
+            SelectionError::MissingRequirement { entry, requirement } => write!(
+                f,
+                "{} requires {}, which is not enabled",
+                entry, requirement
+            ),
+            SelectionError::UnknownRequirement { entry, requirement } => write!(
+                f,
+                "{} requires {}, which is not in any loaded patch set",
+                entry, requirement
+            ),
+            SelectionError::Conflict {
+                entry,
+                conflicts_with,
+            } => write!(
+                f,
+                "{} can't be enabled at the same time as {}",
+                entry, conflicts_with
+            ),
+            SelectionError::InvalidParameter {
+                entry,
+                parameter,
+                reason,
+            } => write!(
+                f,
+                "Invalid value for \"{}\" in {}: {}",
+                parameter, entry, reason
+            ),
+            SelectionError::UnknownSymbol { entry, reason } => {
+                write!(f, "Unable to place a patch in {}: {}", entry, reason)
+            }
+            SelectionError::InvalidRandom { entry, reason } => {
+                write!(f, "Unable to pick a random value in {}: {}", entry, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SelectionError {}
+
+/// Checks that the enabled entries of a patch set respect its option groups. `ExactlyOne` groups
+/// only need a choice when the patch set is being used, ie. when any of its entries are enabled.
+pub fn validate_selection(patch_set: &PatchSet, enabled: &[bool]) -> Vec<SelectionError> {
+    let is_enabled = |i: &usize| enabled.get(*i).cloned().unwrap_or(false);
+    let in_use = (0..patch_set.len()).any(|i| is_enabled(&i));
+
+    let mut errors = Vec::new();
+
+    for item in selection_items(patch_set) {
+        let SelectionItem::Group { group, options } = item else {
+            continue;
+        };
+
+        let chosen: Vec<String> = options
+            .iter()
+            .filter(|i| is_enabled(i))
+            .map(|i| patch_set.entries[*i].name().clone())
+            .collect();
+
+        if chosen.len() > 1 {
+            errors.push(SelectionError::MultipleOptions {
+                patch_set: patch_set.name.clone(),
+                group: group.name,
+                options: chosen,
+            });
+        } else if chosen.is_empty() && in_use && group.selection == OptionGroupSelection::ExactlyOne
+        {
+            errors.push(SelectionError::NoOption {
+                patch_set: patch_set.name.clone(),
+                group: group.name,
+            });
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        patching::{
+            PatchSet,
+            patch_set::{OptionGroup, OptionGroupSelection},
+            selection::{
+                SelectionError, SelectionItem, categorised_selection_items, selection_items,
+                validate_selection,
+            },
+        },
+        test_util::{entry, patch_set},
+    };
+
+    fn get_test_patch_set(selection: OptionGroupSelection) -> PatchSet {
+        let grouped = |name: &str| entry(name, serde_json::json!({ "group": "Aspect ratio" }));
+
+        PatchSet {
+            option_groups: vec![OptionGroup {
+                name: "Aspect ratio".to_string(),
+                description: String::new(),
+                selection,
+            }],
+            ..patch_set(
+                "ghoulies",
+                serde_json::json!([
+                    entry("Uncap frame rate", serde_json::json!({})),
+                    grouped("4:3"),
+                    grouped("16:9"),
+                    entry("Prevent cutscene camera", serde_json::json!({})),
+                    grouped("2.35:1"),
+                ]),
+            )
+        }
+    }
+
+    #[test]
+    fn groups_are_a_single_item() {
+        let items = selection_items(&get_test_patch_set(OptionGroupSelection::AtMostOne));
+
+        assert_eq!(items.len(), 3);
+        assert!(matches!(items[0], SelectionItem::Entry(0)));
+        assert!(
+            matches!(&items[1], SelectionItem::Group { group, options } if group.name == "Aspect ratio" && options == &vec![1, 2, 4])
+        );
+        assert!(matches!(items[2], SelectionItem::Entry(3)));
+    }
+
+    #[test]
+    fn items_are_listed_under_their_categories() {
+        let categorised = |name: &str, category: Option<&str>, order: Option<u32>| {
+            entry(
+                name,
+                serde_json::json!({ "category": category, "order": order }),
+            )
+        };
+
+        let patch_set = PatchSet {
+            categories: vec!["Camera".to_string()],
+            ..patch_set(
+                "ghoulies",
+                serde_json::json!([
+                    categorised("Uncap frame rate", None, None),
+                    categorised("Infinite health", Some("Cheats"), None),
+                    categorised("Prevent cutscene camera", Some("Camera"), None),
+                    categorised("Free camera", Some("Camera"), Some(1)),
+                ]),
+            )
+        };
+
+        let listed: Vec<(Option<String>, Vec<usize>)> = categorised_selection_items(&patch_set)
+            .into_iter()
+            .map(|c| (c.name, c.items.iter().map(|i| i.entries()[0]).collect()))
+            .collect();
+
+        assert_eq!(
+            listed,
+            vec![
+                (Some("Camera".to_string()), vec![3, 2]),
+                (Some("Cheats".to_string()), vec![1]),
+                (None, vec![0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_multiple_options() {
+        let patch_set = get_test_patch_set(OptionGroupSelection::AtMostOne);
+
+        let errors = validate_selection(&patch_set, &[false, true, false, false, true]);
+        assert_eq!(
+            errors,
+            vec![SelectionError::MultipleOptions {
+                patch_set: "ghoulies".to_string(),
+                group: "Aspect ratio".to_string(),
+                options: vec!["4:3".to_string(), "2.35:1".to_string()],
+            }]
+        );
+
+        assert!(validate_selection(&patch_set, &[true, false, true, false, false]).is_empty());
+        assert!(validate_selection(&patch_set, &[true, false, false, false, false]).is_empty());
+    }
+
+    #[test]
+    fn exactly_one_requires_a_choice_when_in_use() {
+        let patch_set = get_test_patch_set(OptionGroupSelection::ExactlyOne);
+
+        assert!(validate_selection(&patch_set, &[false; 5]).is_empty());
+        assert_eq!(
+            validate_selection(&patch_set, &[true, false, false, false, false]),
+            vec![SelectionError::NoOption {
+                patch_set: "ghoulies".to_string(),
+                group: "Aspect ratio".to_string(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,375 @@
+use sha2::{Digest, Sha256};
+
+use crate::patching::{PatchSet, selection::SetSelection};
+
+/// The start of every selection code. The number is the version of the code's format.
+pub const CODE_PREFIX: &str = "XBP1-";
+
+// Crockford's base32, which leaves out letters that are easily mistaken for numbers
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const CHECKSUM_LEN: usize = 2;
+
+/// Encodes the entries enabled in each patch set, and the values chosen for their parameters, as
+/// a short code like "XBP1-0G8MQ4R...". Patch sets with nothing enabled are left out.
+///
+/// Each patch set is identified by a hash of its name and by its version, so a code only works with
+/// the same versions of the patch sets it was made with.
+pub fn encode(selections: &[(&PatchSet, &SetSelection)]) -> String {
+    let selections: Vec<&(&PatchSet, &SetSelection)> = selections
+        .iter()
+        .filter(|(_, selection)| selection.enabled.contains(&true))
+        .collect();
+
+    // The number of patch sets, followed by each one's identity, version, enabled entries and
+    // changed values
+    let mut payload = Vec::new();
+    write_varint(&mut payload, selections.len() as u32);
+
+    for (patch_set, selection) in selections {
+        payload.extend(identity(patch_set));
+        payload.extend([patch_set.version_major, patch_set.version_minor]);
+
+        write_varint(&mut payload, patch_set.len() as u32);
+
+        let mut bits = vec![0u8; patch_set.len().div_ceil(8)];
+        for i in (0..patch_set.len()).filter(|i| selection.is_enabled(*i)) {
+            bits[i / 8] |= 1 << (i % 8);
+        }
+        payload.extend(bits);
+
+        // Only values that differ from their parameter's default are included
+        let values: Vec<(usize, usize, f64)> = patch_set
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| selection.is_enabled(*i))
+            .flat_map(|(i, entry)| {
+                entry
+                    .parameters()
+                    .iter()
+                    .enumerate()
+                    .map(move |(p, parameter)| (i, p, selection.value(i, parameter)))
+                    .filter(move |(_, p, value)| *value != entry.parameters()[*p].default)
+            })
+            .collect();
+
+        write_varint(&mut payload, values.len() as u32);
+
+        for (entry, parameter, value) in values {
+            write_varint(&mut payload, entry as u32);
+            write_varint(&mut payload, parameter as u32);
+            payload.extend(value.to_le_bytes());
+        }
+    }
+
+    let checksum = Sha256::digest(&payload);
+    payload.extend(&checksum[..CHECKSUM_LEN]);
+
+    format!("{}{}", CODE_PREFIX, to_base32(&payload))
+}
+
+/// Decodes a selection code into a selection for each of the loaded patch sets, in the same order.
+/// Patch sets that the code doesn't mention have nothing enabled.
+pub fn decode(code: &str, patch_sets: &[&PatchSet]) -> Result<Vec<SetSelection>, String> {
+    let code = code.trim();
+
+    let body = code
+        .get(..CODE_PREFIX.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(CODE_PREFIX))
+        .map(|_| &code[CODE_PREFIX.len()..])
+        .ok_or("This is not an XBPatch selection code.".to_string())?;
+
+    let mut payload = from_base32(body).ok_or_else(mistyped)?;
+
+    if payload.len() < CHECKSUM_LEN {
+        return Err(mistyped());
+    }
+
+    let checksum = payload.split_off(payload.len() - CHECKSUM_LEN);
+    if Sha256::digest(&payload)[..CHECKSUM_LEN] != checksum[..] {
+        return Err(mistyped());
+    }
+
+    let mut selections: Vec<SetSelection> =
+        patch_sets.iter().map(|ps| SetSelection::new(ps)).collect();
+    let mut reader = Reader { bytes: &payload };
+
+    for _ in 0..reader.varint()? {
+        let id = reader.take(4)?;
+        let (major, minor) = (reader.byte()?, reader.byte()?);
+
+        let named: Vec<usize> = (0..patch_sets.len())
+            .filter(|i| identity(patch_sets[*i])[..] == id[..])
+            .collect();
+
+        let s = named
+            .iter()
+            .copied()
+            .find(|i| {
+                (patch_sets[*i].version_major, patch_sets[*i].version_minor) == (major, minor)
+            })
+            .ok_or_else(|| match named.first() {
+                Some(i) => format!(
+                    "The selection code was made with version {}.{} of \"{}\", but version {}.{} is loaded.",
+                    major,
+                    minor,
+                    patch_sets[*i].name,
+                    patch_sets[*i].version_major,
+                    patch_sets[*i].version_minor
+                ),
+                None => "The selection code uses a patch set that isn't loaded.".to_string(),
+            })?;
+
+        let patch_set = patch_sets[s];
+        let entry_count = reader.varint()? as usize;
+
+        if entry_count != patch_set.len() {
+            return Err(format!(
+                "The selection code was made when \"{}\" had {} entries, but it now has {}.",
+                patch_set.name,
+                entry_count,
+                patch_set.len()
+            ));
+        }
+
+        let bits = reader.take(entry_count.div_ceil(8))?;
+        for (i, enabled) in selections[s].enabled.iter_mut().enumerate() {
+            *enabled = bits[i / 8] & (1 << (i % 8)) != 0;
+        }
+
+        for _ in 0..reader.varint()? {
+            let entry = reader.varint()? as usize;
+            let parameter = reader.varint()? as usize;
+            let value = f64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+
+            let name = patch_set
+                .entries
+                .get(entry)
+                .and_then(|e| e.parameters().get(parameter))
+                .map(|p| p.name.clone())
+                .ok_or_else(mistyped)?;
+
+            selections[s].set_value(entry, &name, value);
+        }
+    }
+
+    Ok(selections)
+}
+
+// A short, fixed length identity for a patch set, taken from its name
+fn identity(patch_set: &PatchSet) -> [u8; 4] {
+    Sha256::digest(patch_set.name.as_bytes())[..4]
+        .try_into()
+        .unwrap()
+}
+
+fn mistyped() -> String {
+    "The selection code has been mistyped.".to_string()
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    bytes.push(value as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < len {
+            return Err(mistyped());
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u32, String> {
+        let mut value = 0u32;
+
+        for shift in (0..32).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u32) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(mistyped())
+    }
+}
+
+fn to_base32(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            text.push(ALPHABET[(buffer >> bits) as usize & 0x1f] as char);
+        }
+    }
+
+    if bits > 0 {
+        text.push(ALPHABET[(buffer << (5 - bits)) as usize & 0x1f] as char);
+    }
+
+    text
+}
+
+// Dashes and spaces are ignored, and letters that look like numbers are read as those numbers
+fn from_base32(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+
+    for c in text.chars().filter(|c| *c != '-' && !c.is_whitespace()) {
+        let c = match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        };
+
+        let value = ALPHABET.iter().position(|a| *a as char == c)? as u32;
+
+        buffer = ((buffer << 5) | value) & 0xfff;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        patching::{
+            PatchSet,
+            selection::SetSelection,
+            selection_code::{CODE_PREFIX, decode, encode},
+        },
+        test_util::{entry, patch_set},
+    };
+
+    fn get_test_patch_set(name: &str, version_minor: u8, entries: usize) -> PatchSet {
+        let entries: Vec<serde_json::Value> = (0..entries)
+            .map(|i| {
+                entry(
+                    &format!("Entry {}", i),
+                    serde_json::json!({
+                        "parameters": [
+                            { "name": "FPS", "value_type": "U16", "default": 30 }
+                        ]
+                    }),
+                )
+            })
+            .collect();
+
+        PatchSet {
+            version_minor,
+            ..patch_set(name, entries.into())
+        }
+    }
+
+    #[test]
+    fn codes_decode_to_the_same_selection() {
+        let ghoulies = get_test_patch_set("ghoulies", 0, 11);
+        let addon = get_test_patch_set("addon", 2, 3);
+        let unused = get_test_patch_set("unused", 0, 2);
+
+        let mut selection = SetSelection::new(&ghoulies);
+        selection.enabled[0] = true;
+        selection.enabled[9] = true;
+        selection.set_value(9, "FPS", 60.0);
+
+        let mut addon_selection = SetSelection::new(&addon);
+        addon_selection.enabled[2] = true;
+
+        let code = encode(&[
+            (&ghoulies, &selection),
+            (&unused, &SetSelection::new(&unused)),
+            (&addon, &addon_selection),
+        ]);
+
+        // Loading the patch sets in another order, and typing the code in lowercase, still works
+        let decoded = decode(&code.to_lowercase(), &[&addon, &ghoulies, &unused]).unwrap();
+
+        assert_eq!(
+            decoded,
+            vec![addon_selection, selection, SetSelection::new(&unused)]
+        );
+    }
+
+    #[test]
+    fn codes_can_have_more_than_255_patch_sets() {
+        let patch_sets: Vec<PatchSet> = (0..300)
+            .map(|i| get_test_patch_set(&format!("set {}", i), 0, 1))
+            .collect();
+        let patch_sets: Vec<&PatchSet> = patch_sets.iter().collect();
+
+        let selections: Vec<SetSelection> = patch_sets
+            .iter()
+            .map(|patch_set| SetSelection {
+                enabled: vec![true],
+                ..SetSelection::new(patch_set)
+            })
+            .collect();
+
+        let code = encode(
+            &patch_sets
+                .iter()
+                .copied()
+                .zip(selections.iter())
+                .collect::<Vec<_>>(),
+        );
+
+        assert_eq!(decode(&code, &patch_sets).unwrap(), selections);
+    }
+
+    #[test]
+    fn codes_need_the_same_versions() {
+        let ghoulies = get_test_patch_set("ghoulies", 0, 4);
+        let mut selection = SetSelection::new(&ghoulies);
+        selection.enabled[1] = true;
+
+        let code = encode(&[(&ghoulies, &selection)]);
+
+        let newer = get_test_patch_set("ghoulies", 1, 4);
+        assert!(
+            decode(&code, &[&newer])
+                .unwrap_err()
+                .contains("version 1.0")
+        );
+
+        let other = get_test_patch_set("other", 0, 4);
+        assert!(decode(&code, &[&other]).is_err());
+
+        let mut mistyped = code.clone();
+        let i = CODE_PREFIX.len() + 3;
+        let typo = if &mistyped[i..i + 1] == "A" { "B" } else { "A" };
+        mistyped.replace_range(i..i + 1, typo);
+        assert_eq!(
+            decode(&mistyped, &[&ghoulies]),
+            Err("The selection code has been mistyped.".to_string())
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use schemars::{Schema, SchemaGenerator, json_schema};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 pub fn de_hex_to_u32<'de, D>(deserializer: D) -> Result<u32, D::Error>
@@ -33,6 +34,17 @@ where
         .collect()
 }
 
+pub fn de_hex_list_to_vvu8<'de, D>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Hex(#[serde(deserialize_with = "de_hex_to_vu8")] Vec<u8>);
+
+    let list: Vec<Hex> = Deserialize::deserialize(deserializer)?;
+    Ok(list.into_iter().map(|Hex(bytes)| bytes).collect())
+}
+
 pub fn de_hex_to_ovu8<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
 where
     D: Deserializer<'de>,
@@ -76,6 +88,16 @@ where
     serializer.serialize_str(&hex_string)
 }
 
+pub fn se_vvu8_to_hex<S>(val: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    #[derive(Serialize)]
+    struct Hex<'a>(#[serde(serialize_with = "se_vu8_to_hex")] &'a Vec<u8>);
+
+    serializer.collect_seq(val.iter().map(Hex))
+}
+
 pub fn se_ovu8_to_hex<S>(val: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -89,6 +111,34 @@ where
     }
 }
 
+pub fn is_zero(val: &i32) -> bool {
+    *val == 0
+}
+
 pub fn get_none<T>() -> Option<T> {
     None
 }
+
+pub fn hex_u32_schema(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "type": "string",
+        "pattern": "^(0x)?[0-9a-fA-F]{1,8}$",
+        "examples": ["0x7ff1c"]
+    })
+}
+
+pub fn hex_bytes_schema(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "type": "string",
+        "pattern": "^(0x)?([0-9a-fA-F]{2})*$",
+        "examples": ["0x9090909090"]
+    })
+}
+
+pub fn hex_bytes_list_schema(generator: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "type": "array",
+        "items": hex_bytes_schema(generator),
+        "examples": [["0x01", "0x04", "0x07"]]
+    })
+}
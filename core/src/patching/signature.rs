@@ -0,0 +1,137 @@
+use std::{borrow::Cow, fmt::Display, str::FromStr};
+
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::{Deserialize, Serialize};
+
+/// A sequence of bytes to search for, where `None` matches any byte. Written as hex bytes
+/// separated by spaces, with `??` as a wildcard, eg. `8B 45 ?? 85 C0 74 ??`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct BytePattern(Vec<Option<u8>>);
+
+impl BytePattern {
+    /// A pattern that matches these bytes and nothing else.
+    pub fn exact(bytes: Vec<u8>) -> BytePattern {
+        BytePattern(bytes.into_iter().map(Some).collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn matches_at(&self, haystack: &[u8], start: usize) -> bool {
+        haystack.len() >= start + self.len()
+            && self
+                .0
+                .iter()
+                .zip(&haystack[start..])
+                .all(|(p, b)| p.is_none_or(|p| p == *b))
+    }
+
+    /// Every position in `haystack` where the pattern starts.
+    pub fn find_all(&self, haystack: &[u8]) -> Vec<usize> {
+        if self.is_empty() || haystack.len() < self.len() {
+            return Vec::new();
+        }
+
+        (0..=haystack.len() - self.len())
+            .filter(|i| self.matches_at(haystack, *i))
+            .collect()
+    }
+}
+
+impl FromStr for BytePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = s
+            .split_whitespace()
+            .map(|token| match token {
+                "?" | "??" => Ok(None),
+                _ if token.len() == 2 => u8::from_str_radix(token, 16)
+                    .map(Some)
+                    .map_err(|_| format!("\"{}\" is not a hex byte or ??", token)),
+                _ => Err(format!("\"{}\" is not a hex byte or ??", token)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if bytes.iter().all(Option::is_none) {
+            return Err("a pattern needs at least one byte that isn't a wildcard".to_string());
+        }
+
+        Ok(BytePattern(bytes))
+    }
+}
+
+impl TryFrom<String> for BytePattern {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<BytePattern> for String {
+    fn from(value: BytePattern) -> Self {
+        value.to_string()
+    }
+}
+
+impl JsonSchema for BytePattern {
+    fn schema_name() -> Cow<'static, str> {
+        "BytePattern".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "pattern": "^\\s*([0-9a-fA-F]{2}|\\?\\??)(\\s+([0-9a-fA-F]{2}|\\?\\??))*\\s*$",
+            "examples": ["8B 45 ?? 85 C0 74 ??"]
+        })
+    }
+}
+
+impl Display for BytePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tokens: Vec<String> = self
+            .0
+            .iter()
+            .map(|b| match b {
+                Some(b) => format!("{:02X}", b),
+                None => "??".to_string(),
+            })
+            .collect();
+
+        write!(f, "{}", tokens.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::patching::signature::BytePattern;
+
+    #[test]
+    fn parses_and_prints_wildcards() {
+        let pattern: BytePattern = "8b 45 ?? 85 C0 74 ?".parse().unwrap();
+
+        assert_eq!(pattern.len(), 7);
+        assert_eq!(pattern.to_string(), "8B 45 ?? 85 C0 74 ??");
+
+        assert!("8B 4".parse::<BytePattern>().is_err());
+        assert!("8B XX".parse::<BytePattern>().is_err());
+        assert!("?? ??".parse::<BytePattern>().is_err());
+    }
+
+    #[test]
+    fn finds_every_match() {
+        let pattern: BytePattern = "74 ?? 90".parse().unwrap();
+        let haystack = [0x74, 0x01, 0x90, 0x74, 0xff, 0x90, 0x74, 0x00];
+
+        assert_eq!(pattern.find_all(&haystack), vec![0, 3]);
+        assert_eq!(pattern.find_all(&haystack[1..5]), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,506 @@
+use std::{
+    fmt::Display,
+    path::{Component, Path, PathBuf},
+};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{game_files::is_game_file_path, patching::include};
+
+/// Added to the end of a patch set's file name to get its signature's, eg. "ghoulies.json.sig".
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// The usual name of the keyring, which is kept alongside the user's patch sets.
+pub const KEYRING_FILE_NAME: &str = "keyring.json";
+
+// Signed ahead of the list of files, so a signature can't be mistaken for one of anything else
+const SIGNED_MESSAGE_HEADER: &str = "xbpatch signature 1\n";
+
+/// A file covered by a signature, and its hash when it was signed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SignedFile {
+    // Relative to the patch set's folder, using forward slashes
+    pub path: String,
+
+    // SHA-256 of the file's contents, as 64 hex digits
+    pub sha256: String,
+}
+
+/// A detached signature, stored next to the patch set it signs. It covers the patch set's JSON
+/// file and those of the patch sets it includes, along with every file they copy into the game.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PatchSetSignature {
+    // The name of whoever signed the patch set, as given in their key file. This is only a
+    // claim until their public key is in the user's keyring.
+    pub signer: String,
+
+    // The signer's ed25519 public key, as 64 hex digits
+    pub public_key: String,
+
+    pub files: Vec<SignedFile>,
+
+    // The ed25519 signature of the files' hashes, as 128 hex digits
+    pub signature: String,
+}
+
+/// An author's private key, used to sign their patch sets. This should never be shared.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SigningKeyFile {
+    pub name: String,
+
+    // The ed25519 secret key, as 64 hex digits
+    pub secret_key: String,
+}
+
+/// An author whose patch sets the user trusts.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TrustedKey {
+    pub name: String,
+    pub public_key: String,
+}
+
+/// The authors the user trusts, kept in a JSON file of their own.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Keyring {
+    pub trusted: Vec<TrustedKey>,
+}
+
+/// Whether a patch set can be shown to come from someone the user trusts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trust {
+    Unsigned,
+
+    // Signed by a key in the keyring, with the name the keyring gives it
+    Trusted(String),
+
+    // Signed, but by a key that isn't in the keyring, with the name the signer gave themselves
+    Untrusted(String),
+
+    // The signature doesn't match the files, with the reason why
+    Tampered(String),
+}
+
+impl Display for Trust {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Trust::Unsigned => write!(f, "Unsigned"),
+            Trust::Trusted(name) => write!(f, "Signed by {}", name),
+            Trust::Untrusted(name) => write!(f, "Signed by \"{}\", who isn't in the keyring", name),
+            Trust::Tampered(reason) => write!(f, "Tampered with: {}", reason),
+        }
+    }
+}
+
+impl SigningKeyFile {
+    /// Makes a new random key.
+    pub fn generate(name: String) -> Result<SigningKeyFile, std::io::Error> {
+        let mut secret_key = [0u8; 32];
+        getrandom::fill(&mut secret_key).map_err(|e| {
+            std::io::Error::other(format!("Unable to generate a random key. Error: {}", e))
+        })?;
+
+        Ok(SigningKeyFile {
+            name,
+            secret_key: to_hex(&secret_key),
+        })
+    }
+
+    pub fn from_file(path: &PathBuf) -> Result<SigningKeyFile, std::io::Error> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    fn signing_key(&self) -> Result<SigningKey, std::io::Error> {
+        let secret_key = from_hex(&self.secret_key)
+            .and_then(|b| b.try_into().ok())
+            .ok_or(std::io::Error::other("The secret key isn't 64 hex digits."))?;
+
+        Ok(SigningKey::from_bytes(&secret_key))
+    }
+
+    /// The public key to add to the keyrings of people who trust this author.
+    pub fn trusted_key(&self) -> Result<TrustedKey, std::io::Error> {
+        Ok(TrustedKey {
+            name: self.name.clone(),
+            public_key: to_hex(self.signing_key()?.verifying_key().as_bytes()),
+        })
+    }
+}
+
+impl Keyring {
+    /// Loads a keyring, which is empty if the file doesn't exist yet.
+    pub fn from_file(path: &Path) -> Result<Keyring, std::io::Error> {
+        if !path.exists() {
+            return Ok(Keyring::default());
+        }
+
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    /// The name the keyring gives to a public key, if it is trusted.
+    pub fn name_of(&self, public_key: &str) -> Option<&str> {
+        self.trusted
+            .iter()
+            .find(|k| k.public_key.eq_ignore_ascii_case(public_key))
+            .map(|k| k.name.as_str())
+    }
+}
+
+/// Where a patch set's signature is kept.
+pub fn signature_path(patch_set_path: &Path) -> PathBuf {
+    let mut path = patch_set_path.as_os_str().to_owned();
+    path.push(format!(".{}", SIGNATURE_EXTENSION));
+
+    PathBuf::from(path)
+}
+
+/// Signs a patch set, the patch sets it includes and the files they copy into the game, writing
+/// the signature next to it.
+pub fn sign_patch_set(
+    patch_set_path: &PathBuf,
+    key: &SigningKeyFile,
+) -> Result<PatchSetSignature, std::io::Error> {
+    let folder = patch_set_path.parent().unwrap_or(Path::new(""));
+    let paths = signed_paths(patch_set_path)?;
+
+    let files = paths
+        .into_iter()
+        .map(|path| {
+            let contents = std::fs::read(folder.join(&path)).map_err(|e| {
+                std::io::Error::other(format!("Unable to read {}. Error: {}", path, e))
+            })?;
+
+            Ok(SignedFile {
+                sha256: sha256_hex(&contents),
+                path,
+            })
+        })
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+
+    let signing_key = key.signing_key()?;
+
+    let signature = PatchSetSignature {
+        signer: key.name.clone(),
+        public_key: to_hex(signing_key.verifying_key().as_bytes()),
+        signature: to_hex(&signing_key.sign(&signed_message(&files)).to_bytes()),
+        files,
+    };
+
+    std::fs::write(
+        signature_path(patch_set_path),
+        serde_json::to_vec_pretty(&signature)?,
+    )?;
+
+    Ok(signature)
+}
+
+// The patch set's own file, the files of every patch set it includes, and every file their entries
+// copy into the game, relative to the patch set's folder
+fn signed_paths(patch_set_path: &PathBuf) -> Result<Vec<String>, std::io::Error> {
+    let folder = patch_set_path.parent().unwrap_or(Path::new(""));
+    let patch_set = include::load_with_includes(patch_set_path)?;
+
+    let mut paths = vec![file_name_of(patch_set_path)?];
+
+    for included in include::included_paths(patch_set_path)? {
+        paths.push(relative_path(folder, &included)?);
+    }
+
+    for entry in &patch_set.entries {
+        // Included entries find their files relative to the patch set they were written in
+        let entry_folder = match entry.included_from() {
+            Some(path) => path.parent().unwrap_or(Path::new("")),
+            None => folder,
+        };
+
+        for source in entry.files().iter().filter_map(|c| c.source.as_ref()) {
+            if !is_game_file_path(source) {
+                return Err(std::io::Error::other(format!(
+                    "\"{}\" is not a path inside the patch set's folder.",
+                    source
+                )));
+            }
+
+            let source = relative_path(folder, &entry_folder.join(source.replace('\\', "/")))?;
+
+            if !paths.contains(&source) {
+                paths.push(source);
+            }
+        }
+    }
+
+    Ok(paths)
+}
+
+// Included patch sets are found by joining paths onto the patch set's folder, so they always start
+// with it, even when they then leave it with ".."
+fn relative_path(folder: &Path, path: &Path) -> Result<String, std::io::Error> {
+    let relative = path.strip_prefix(folder).map_err(|_| {
+        std::io::Error::other(format!(
+            "{} isn't relative to the patch set's folder.",
+            path.display()
+        ))
+    })?;
+
+    Ok(relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+// Files of included patch sets can be outside the patch set's folder, but never anywhere absolute
+fn is_relative_path(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::ParentDir))
+}
+
+/// Checks a patch set's signature against its files and the user's keyring.
+pub fn verify_patch_set(patch_set_path: &Path, keyring: &Keyring) -> Trust {
+    let path = signature_path(patch_set_path);
+
+    if !path.exists() {
+        return Trust::Unsigned;
+    }
+
+    let signature = match std::fs::read(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|c| serde_json::from_slice(&c).map_err(|e| e.to_string()))
+    {
+        Ok(s) => s,
+        Err(e) => return Trust::Tampered(format!("the signature can't be read ({})", e)),
+    };
+
+    let Ok(name) = file_name_of(patch_set_path) else {
+        return Trust::Tampered("the patch set's file name isn't valid".to_string());
+    };
+
+    let folder = patch_set_path.parent().unwrap_or(Path::new(""));
+
+    let trust = verify_signature(
+        &signature,
+        &name,
+        |path| {
+            if !is_relative_path(path) {
+                return Err(std::io::Error::other(format!(
+                    "\"{}\" isn't relative to the patch set",
+                    path
+                )));
+            }
+
+            std::fs::read(folder.join(path))
+        },
+        keyring,
+    );
+
+    if let Trust::Tampered(_) = trust {
+        return trust;
+    }
+
+    // Every patch set it includes must be signed too, or they could be changed unnoticed
+    let included = match include::included_paths(patch_set_path) {
+        Ok(paths) => paths,
+        Err(e) => {
+            return Trust::Tampered(format!("the patch sets it includes can't be read ({})", e));
+        }
+    };
+
+    for path in included {
+        let covered = relative_path(folder, &path)
+            .is_ok_and(|relative| signature.files.iter().any(|f| f.path == relative));
+
+        if !covered {
+            return Trust::Tampered(format!("{} isn't covered by the signature", path.display()));
+        }
+    }
+
+    trust
+}
+
+/// Checks a signature against the files it lists, which are read relative to the patch set's
+/// folder. Reading a file the patch set shouldn't have signed must fail.
+pub(crate) fn verify_signature(
+    signature: &PatchSetSignature,
+    patch_set_name: &str,
+    mut read: impl FnMut(&str) -> Result<Vec<u8>, std::io::Error>,
+    keyring: &Keyring,
+) -> Trust {
+    let public_key = from_hex(&signature.public_key)
+        .and_then(|b| b.try_into().ok())
+        .and_then(|b| VerifyingKey::from_bytes(&b).ok());
+
+    let signed = from_hex(&signature.signature)
+        .and_then(|b| b.try_into().ok())
+        .map(|b| Signature::from_bytes(&b));
+
+    let (Some(public_key), Some(signed)) = (public_key, signed) else {
+        return Trust::Tampered("the signature isn't a valid ed25519 signature".to_string());
+    };
+
+    if public_key
+        .verify_strict(&signed_message(&signature.files), &signed)
+        .is_err()
+    {
+        return Trust::Tampered("the signature doesn't match the files it lists".to_string());
+    }
+
+    if !signature.files.iter().any(|f| f.path == patch_set_name) {
+        return Trust::Tampered(format!("{} isn't covered by the signature", patch_set_name));
+    }
+
+    for file in &signature.files {
+        match read(&file.path) {
+            Ok(contents) if sha256_hex(&contents).eq_ignore_ascii_case(&file.sha256) => (),
+            Ok(_) => {
+                return Trust::Tampered(format!("{} has changed since it was signed", file.path));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Trust::Tampered(format!("{} is missing", file.path));
+            }
+            Err(e) => return Trust::Tampered(format!("{} can't be read ({})", file.path, e)),
+        }
+    }
+
+    match keyring.name_of(&signature.public_key) {
+        Some(name) => Trust::Trusted(name.to_string()),
+        None => Trust::Untrusted(signature.signer.clone()),
+    }
+}
+
+fn signed_message(files: &[SignedFile]) -> Vec<u8> {
+    let mut message = SIGNED_MESSAGE_HEADER.to_string();
+
+    for file in files {
+        message.push_str(&format!("{} {}\n", file.sha256.to_lowercase(), file.path));
+    }
+
+    message.into_bytes()
+}
+
+fn file_name_of(path: &Path) -> Result<String, std::io::Error> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(str::to_string)
+        .ok_or(std::io::Error::other(
+            "The patch set's file name isn't valid.",
+        ))
+}
+
+fn sha256_hex(contents: &[u8]) -> String {
+    to_hex(&Sha256::digest(contents))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        patching::signing::{Keyring, SigningKeyFile, Trust, sign_patch_set, verify_patch_set},
+        test_util::{entry, patch_set_json, temp_folder, write_music_swap_patch_set},
+    };
+
+    #[test]
+    fn signatures_are_checked_against_the_keyring() {
+        let path =
+            write_music_swap_patch_set(&temp_folder("signatures_are_checked_against_the_keyring"));
+        let key = SigningKeyFile::generate("hethan".to_string()).unwrap();
+
+        let mut keyring = Keyring::default();
+        assert_eq!(verify_patch_set(&path, &keyring), Trust::Unsigned);
+
+        sign_patch_set(&path, &key).unwrap();
+        assert_eq!(
+            verify_patch_set(&path, &keyring),
+            Trust::Untrusted("hethan".to_string())
+        );
+
+        let mut trusted = key.trusted_key().unwrap();
+        trusted.name = "hethan/rectangle".to_string();
+        keyring.trusted.push(trusted);
+
+        assert_eq!(
+            verify_patch_set(&path, &keyring),
+            Trust::Trusted("hethan/rectangle".to_string())
+        );
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn changed_files_are_noticed() {
+        let path = write_music_swap_patch_set(&temp_folder("changed_files_are_noticed"));
+        let key = SigningKeyFile::generate("hethan".to_string()).unwrap();
+
+        sign_patch_set(&path, &key).unwrap();
+        std::fs::write(path.with_file_name("assets/title.wma"), b"malware").unwrap();
+
+        assert_eq!(
+            verify_patch_set(&path, &Keyring::default()),
+            Trust::Tampered("assets/title.wma has changed since it was signed".to_string())
+        );
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn changed_includes_are_noticed() {
+        let path = write_music_swap_patch_set(&temp_folder("changed_includes_are_noticed"));
+        let folder = path.parent().unwrap();
+
+        let base = patch_set_json(
+            "engine base",
+            serde_json::json!([entry(
+                "Uncap frame rate",
+                serde_json::json!({
+                    "patches": [
+                        { "offset": "0x1000", "offset_type": "Raw", "replacement_bytes": "0x90" }
+                    ]
+                })
+            )]),
+        );
+        std::fs::write(folder.join("base.json"), base.to_string()).unwrap();
+
+        let mut patch_set: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        patch_set["includes"] = serde_json::json!([{ "path": "base.json" }]);
+        std::fs::write(&path, patch_set.to_string()).unwrap();
+
+        let key = SigningKeyFile::generate("hethan".to_string()).unwrap();
+        let signature = sign_patch_set(&path, &key).unwrap();
+
+        let signed: Vec<&str> = signature.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(
+            signed,
+            vec!["ghoulies.json", "base.json", "assets/title.wma"]
+        );
+
+        std::fs::write(
+            folder.join("base.json"),
+            base.to_string().replace("0x90", "0xcc"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            verify_patch_set(&path, &Keyring::default()),
+            Trust::Tampered("base.json has changed since it was signed".to_string())
+        );
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+}
@@ -0,0 +1,61 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::patching::serialization::*;
+
+/// A named virtual address, such as a function named in a disassembler. Patches can be written
+/// relative to a symbol, so porting a patch set to another build only means updating its symbols.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+
+    #[serde(serialize_with = "se_u32_to_hex", deserialize_with = "de_hex_to_u32")]
+    #[schemars(schema_with = "hex_u32_schema")]
+    pub address: u32,
+
+    // Addresses for builds of the game where the symbol has moved
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub revisions: Vec<SymbolRevision>,
+}
+
+/// Where a symbol is found in one build of the game, identified by the version in its XBE
+/// certificate.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct SymbolRevision {
+    pub version: u32,
+
+    #[serde(serialize_with = "se_u32_to_hex", deserialize_with = "de_hex_to_u32")]
+    #[schemars(schema_with = "hex_u32_schema")]
+    pub address: u32,
+}
+
+impl Symbol {
+    /// The address of the symbol in the build with the given XBE version.
+    pub fn address_for(&self, version: u32) -> u32 {
+        self.revisions
+            .iter()
+            .find(|r| r.version == version)
+            .map(|r| r.address)
+            .unwrap_or(self.address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::patching::symbol::Symbol;
+
+    #[test]
+    fn revisions_override_the_address() {
+        let symbol: Symbol = serde_json::from_value(serde_json::json!({
+            "name": "PickReplayScene",
+            "address": "0x37907d",
+            "revisions": [
+                { "version": 2, "address": "0x3790fd" }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(symbol.address_for(1), 0x37907d);
+        assert_eq!(symbol.address_for(2), 0x3790fd);
+    }
+}
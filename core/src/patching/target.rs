@@ -0,0 +1,181 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{patching::serialization::*, xbe::XBEIdentity};
+
+/// The regions a game can be released for, as flagged in its XBE certificate.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+pub enum GameRegion {
+    NorthAmerica,
+    Japan,
+    RestOfWorld,
+}
+
+impl GameRegion {
+    pub fn flag(&self) -> u32 {
+        match self {
+            GameRegion::NorthAmerica => 0x1,
+            GameRegion::Japan => 0x2,
+            GameRegion::RestOfWorld => 0x4,
+        }
+    }
+}
+
+/// Details that tell builds of the same game apart. Details that are left out match any build.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq)]
+pub struct BuildDetails {
+    // The version from the XBE certificate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<GameRegion>,
+
+    // SHA-1 of the whole unmodified XBE, as 40 hex digits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+}
+
+impl BuildDetails {
+    pub fn matches(&self, xbe: &XBEIdentity) -> bool {
+        self.version.is_none_or(|v| v == xbe.version)
+            && (self.regions.is_empty() || self.regions.iter().any(|r| r.flag() & xbe.region != 0))
+            && self
+                .sha1
+                .as_ref()
+                .is_none_or(|h| h.eq_ignore_ascii_case(&xbe.sha1))
+    }
+}
+
+/// A build of a game that a patch set is known to work with. Only the title ID is required, and
+/// any other details that are given must all match the XBE.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq)]
+pub struct PatchTarget {
+    // Shown to users, eg. "PAL v1.0"
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+
+    #[serde(serialize_with = "se_u32_to_hex", deserialize_with = "de_hex_to_u32")]
+    #[schemars(schema_with = "hex_u32_schema")]
+    pub title_id: u32,
+
+    #[serde(flatten)]
+    pub build: BuildDetails,
+}
+
+impl PatchTarget {
+    pub fn matches(&self, xbe: &XBEIdentity) -> bool {
+        self.title_id == xbe.title_id && self.build.matches(xbe)
+    }
+}
+
+/// How well an XBE matches the targets of a patch set.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TargetMatch {
+    // The XBE is one of the targets, or the patch set doesn't list any
+    Matches,
+
+    // The XBE is the right game, but not a build the patch set is known to work with
+    UnknownBuild(String),
+
+    // The XBE is a different game entirely
+    WrongGame(String),
+}
+
+/// Compares an XBE against the builds a patch set is known to work with.
+pub fn check_targets(
+    patch_set_name: &str,
+    targets: &[PatchTarget],
+    xbe: &XBEIdentity,
+) -> TargetMatch {
+    if targets.is_empty() || targets.iter().any(|t| t.matches(xbe)) {
+        return TargetMatch::Matches;
+    }
+
+    if targets.iter().any(|t| t.title_id == xbe.title_id) {
+        TargetMatch::UnknownBuild(format!(
+            "{} isn't known to work with this build of the game ({})",
+            patch_set_name, xbe
+        ))
+    } else {
+        let title_ids: Vec<String> = targets
+            .iter()
+            .map(|t| format!("{:08X}", t.title_id))
+            .collect();
+
+        TargetMatch::WrongGame(format!(
+            "{} is for title ID {}, but the XBE has title ID {:08X}",
+            patch_set_name,
+            title_ids.join(" or "),
+            xbe.title_id
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        patching::target::{BuildDetails, GameRegion, PatchTarget, TargetMatch, check_targets},
+        xbe::XBEIdentity,
+    };
+
+    fn ghoulies_pal() -> XBEIdentity {
+        XBEIdentity {
+            title_id: 0x4d530051,
+            version: 1,
+            region: 0x4,
+            sha1: "da39a3ee5e6b4b0d3255bfef95601890afd80709".to_string(),
+        }
+    }
+
+    #[test]
+    fn compares_every_given_detail() {
+        let mut target = PatchTarget {
+            title_id: 0x4d530051,
+            build: BuildDetails {
+                regions: vec![GameRegion::NorthAmerica, GameRegion::RestOfWorld],
+                sha1: Some("DA39A3EE5E6B4B0D3255BFEF95601890AFD80709".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert!(target.matches(&ghoulies_pal()));
+
+        target.build.version = Some(2);
+        assert!(!target.matches(&ghoulies_pal()));
+    }
+
+    #[test]
+    fn refuses_other_games() {
+        let ghoulies = PatchTarget {
+            title_id: 0x4d530051,
+            build: BuildDetails {
+                version: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(
+            check_targets("ghoulies", &[], &ghoulies_pal()),
+            TargetMatch::Matches
+        );
+        assert!(matches!(
+            check_targets("ghoulies", &[ghoulies.clone()], &ghoulies_pal()),
+            TargetMatch::UnknownBuild(_)
+        ));
+
+        let other_game = XBEIdentity {
+            title_id: 0x4d530004,
+            ..ghoulies_pal()
+        };
+
+        assert_eq!(
+            check_targets("ghoulies", &[ghoulies], &other_game),
+            TargetMatch::WrongGame(
+                "ghoulies is for title ID 4D530051, but the XBE has title ID 4D530004".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::patching::{Patch, PatchOffsetType};
+
+/// How the characters of a string are stored in the game.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq)]
+pub enum TextEncoding {
+    // One byte per character, for plain English text
+    #[default]
+    Ascii,
+
+    // Two bytes per character, as used by the Xbox dashboard and most localised games
+    Utf16Le,
+}
+
+impl TextEncoding {
+    /// The bytes of the null character that ends a string.
+    pub fn terminator(&self) -> &'static [u8] {
+        match self {
+            TextEncoding::Ascii => &[0],
+            TextEncoding::Utf16Le => &[0, 0],
+        }
+    }
+
+    /// The bytes of a string, without a terminator.
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, String> {
+        if text.contains('\0') {
+            return Err("the text can't contain null characters".to_string());
+        }
+
+        match self {
+            TextEncoding::Ascii => match text.chars().find(|c| !c.is_ascii()) {
+                Some(c) => Err(format!("'{}' can't be written as ASCII", c)),
+                None => Ok(text.as_bytes().to_vec()),
+            },
+            TextEncoding::Utf16Le => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+        }
+    }
+
+    /// The bytes of a string followed by its terminator.
+    pub fn encode_terminated(&self, text: &str) -> Result<Vec<u8>, String> {
+        let mut bytes = self.encode(text)?;
+        bytes.extend(self.terminator());
+
+        Ok(bytes)
+    }
+}
+
+/// What fills the space left after a string that is shorter than the one it replaces.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq)]
+pub enum TextPadding {
+    // Null characters, which also end the string
+    #[default]
+    Null,
+
+    // Spaces, for fixed width text that isn't null terminated
+    Space,
+}
+
+/// Encodes a string to fill exactly `available` bytes. Fails if the string doesn't fit, including
+/// its terminator when padded with nulls.
+pub fn encode_to_fit(
+    text: &str,
+    encoding: TextEncoding,
+    padding: TextPadding,
+    available: usize,
+) -> Result<Vec<u8>, String> {
+    let mut bytes = match padding {
+        TextPadding::Null => encoding.encode_terminated(text)?,
+        TextPadding::Space => encoding.encode(text)?,
+    };
+
+    if bytes.len() > available {
+        return Err(format!(
+            "\"{}\" takes up {} bytes, but only {} are available",
+            text,
+            bytes.len(),
+            available
+        ));
+    }
+
+    let pad = match padding {
+        TextPadding::Null => encoding.terminator().to_vec(),
+        TextPadding::Space => encoding.encode(" ")?,
+    };
+
+    // Space padding can't split a character, so any odd byte left over is a null
+    while bytes.len() + pad.len() <= available {
+        bytes.extend(&pad);
+    }
+    bytes.resize(available, 0);
+
+    Ok(bytes)
+}
+
+/// How many bytes a text patch can write. This is `max_length` if it is given, or else the rest of
+/// the string found by a Text offset, or else the length of original_bytes.
+pub fn available_length(patch: &Patch, max_length: Option<usize>) -> Option<usize> {
+    let found = match &patch.offset_type {
+        PatchOffsetType::Text { text, encoding, .. } => encoding
+            .encode_terminated(text)
+            .ok()
+            .and_then(|b| b.len().checked_sub(patch.offset as usize)),
+        _ => None,
+    };
+
+    max_length
+        .or(found)
+        .or(patch.original_bytes.as_ref().map(|b| b.len()))
+}
+
+/// The bytes written by a text patch, which fill all of the space available to it.
+pub fn text_patch_bytes(
+    patch: &Patch,
+    text: &str,
+    encoding: TextEncoding,
+    padding: TextPadding,
+    max_length: Option<usize>,
+) -> Result<Vec<u8>, std::io::Error> {
+    let available = available_length(patch, max_length).ok_or(std::io::Error::other(format!(
+        "The text patch at 0x{:X} needs a max_length, since there is no string or original_bytes to replace.",
+        patch.offset
+    )))?;
+
+    encode_to_fit(text, encoding, padding, available).map_err(|e| {
+        std::io::Error::other(format!(
+            "Unable to write the text patch at 0x{:X}: {}.",
+            patch.offset, e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::patching::text::{TextEncoding, TextPadding, encode_to_fit};
+
+    #[test]
+    fn pads_to_the_available_length() {
+        assert_eq!(
+            encode_to_fit("Hi", TextEncoding::Ascii, TextPadding::Null, 5),
+            Ok(b"Hi\0\0\0".to_vec())
+        );
+        assert_eq!(
+            encode_to_fit("Hi", TextEncoding::Ascii, TextPadding::Space, 4),
+            Ok(b"Hi  ".to_vec())
+        );
+        assert_eq!(
+            encode_to_fit("Hé", TextEncoding::Utf16Le, TextPadding::Null, 8),
+            Ok(vec![0x48, 0, 0xe9, 0, 0, 0, 0, 0])
+        );
+    }
+
+    #[test]
+    fn refuses_text_that_doesnt_fit() {
+        assert!(encode_to_fit("Hello", TextEncoding::Ascii, TextPadding::Null, 5).is_err());
+        assert!(encode_to_fit("Hello", TextEncoding::Ascii, TextPadding::Space, 5).is_ok());
+        assert!(encode_to_fit("Hé", TextEncoding::Ascii, TextPadding::Null, 8).is_err());
+    }
+}
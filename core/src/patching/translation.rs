@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Translations keyed by language code, eg. "ja" or "fr-FR".
+pub type Translations = BTreeMap<String, Translation>;
+
+/// A name and description in another language. Anything left out falls back to the untranslated
+/// text.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq)]
+pub struct Translation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// The language the user's system is set to, as a language code like "ja-JP".
+pub fn user_language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| normalise_language(&value))
+}
+
+/// Turns a locale like "ja_JP.UTF-8" into a language code like "ja-JP". Returns None for locales
+/// that don't name a language, like "C".
+pub fn normalise_language(locale: &str) -> Option<String> {
+    let language = locale
+        .split(['.', '@'])
+        .next()
+        .unwrap_or_default()
+        .replace('_', "-");
+
+    if language.is_empty() || language == "C" || language == "POSIX" {
+        return None;
+    }
+
+    Some(language)
+}
+
+/// Whether a translation's key looks like a language code, eg. "ja" or "pt-BR".
+pub fn is_language_code(code: &str) -> bool {
+    code.split('-')
+        .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Picks one field of the translation that best matches a language, falling back from a regional
+/// language like "fr-CA" to "fr", and then to the untranslated text.
+pub fn localise<'a>(
+    translations: &'a Translations,
+    language: Option<&str>,
+    field: impl Fn(&'a Translation) -> Option<&'a String>,
+    untranslated: &'a str,
+) -> &'a str {
+    let Some(language) = language else {
+        return untranslated;
+    };
+
+    let primary = language.split('-').next().unwrap_or(language);
+
+    [language, primary]
+        .iter()
+        .find_map(|code| {
+            translations
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(code))
+                .and_then(|(_, translation)| field(translation))
+        })
+        .map(String::as_str)
+        .unwrap_or(untranslated)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::patching::translation::{
+        Translation, Translations, is_language_code, localise, normalise_language,
+    };
+
+    #[test]
+    fn falls_back_to_less_specific_languages() {
+        let translations = Translations::from([
+            (
+                "fr".to_string(),
+                Translation {
+                    name: Some("Désactiver le faucheur".to_string()),
+                    description: Some("Empêche le faucheur d'apparaître.".to_string()),
+                },
+            ),
+            (
+                "fr-CA".to_string(),
+                Translation {
+                    name: Some("Désactiver la faucheuse".to_string()),
+                    description: None,
+                },
+            ),
+        ]);
+
+        let name = |language| {
+            localise(
+                &translations,
+                language,
+                |t| t.name.as_ref(),
+                "Disable reaper",
+            )
+        };
+        let description = |language| {
+            localise(
+                &translations,
+                language,
+                |t| t.description.as_ref(),
+                "Stop the reaper from spawning.",
+            )
+        };
+
+        assert_eq!(name(Some("fr-CA")), "Désactiver la faucheuse");
+        assert_eq!(name(Some("fr-FR")), "Désactiver le faucheur");
+        assert_eq!(name(Some("ja")), "Disable reaper");
+        assert_eq!(name(None), "Disable reaper");
+        assert_eq!(
+            description(Some("fr-ca")),
+            "Empêche le faucheur d'apparaître."
+        );
+    }
+
+    #[test]
+    fn locales_become_language_codes() {
+        assert_eq!(normalise_language("ja_JP.UTF-8"), Some("ja-JP".to_string()));
+        assert_eq!(normalise_language("de_DE@euro"), Some("de-DE".to_string()));
+        assert_eq!(normalise_language("C.UTF-8"), None);
+
+        assert!(is_language_code("pt-BR"));
+        assert!(!is_language_code("pt_BR"));
+        assert!(!is_language_code(""));
+    }
+}
@@ -0,0 +1,844 @@
+use std::{collections::HashSet, fmt::Display, fs::File, path::PathBuf};
+
+use serde_json::Value;
+
+use crate::{
+    HasPatches,
+    game_files::is_game_file_path,
+    patching::{
+        EntryReference, PatchContents, PatchOffsetType, PatchSet, include, operation, schema,
+        serialization::*,
+        text,
+        translation::{self, Translations},
+    },
+};
+
+/// A problem with a patch set file, and where in the file it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationProblem {
+    // Written like "entries[2].patches[0].replacement_bytes"
+    pub path: String,
+    pub message: String,
+}
+
+impl ValidationProblem {
+    pub(crate) fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        ValidationProblem {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ValidationProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Checks a patch set file for problems, along with the patch sets it includes.
+pub fn validate_file(path: &PathBuf) -> Result<Vec<ValidationProblem>, std::io::Error> {
+    let value: Value = serde_json::from_reader(File::open(path)?)?;
+    let problems = validate(&value);
+
+    if !problems.is_empty() {
+        return Ok(problems);
+    }
+
+    let patch_set = PatchSet::from_file(path)?;
+
+    if patch_set.includes.is_empty() {
+        return Ok(problems);
+    }
+
+    // Entries and symbols can be referred to once everything has been included
+    let resolved = include::resolve_includes(patch_set.clone(), path);
+
+    // Only what the included patch sets have, to find what the patch set replaces
+    let included = include::resolve_includes(
+        PatchSet {
+            includes: patch_set.includes.clone(),
+            ..Default::default()
+        },
+        path,
+    );
+
+    match (resolved, included) {
+        (Ok(resolved), Ok(included)) => {
+            let mut problems = check(&patch_set, Some(&resolved));
+            check_overrides(&mut problems, &patch_set, &included);
+
+            Ok(problems)
+        }
+        (Err(e), _) | (_, Err(e)) => Ok(vec![ValidationProblem::new("includes", e.to_string())]),
+    }
+}
+
+// Included patches refer to symbols and option groups by name, so replacing one changes how they
+// apply. That's sometimes wanted, but too easy to do by accident to go unmentioned.
+fn check_overrides(
+    problems: &mut Vec<ValidationProblem>,
+    patch_set: &PatchSet,
+    included: &PatchSet,
+) {
+    for (i, symbol) in patch_set.symbols.iter().enumerate() {
+        if included.symbol(&symbol.name).is_some() {
+            problems.push(ValidationProblem::new(
+                format!("symbols[{}].name", i),
+                format!(
+                    "this replaces the symbol named \"{}\" in an included patch set",
+                    symbol.name
+                ),
+            ));
+        }
+    }
+
+    for (i, group) in patch_set.option_groups.iter().enumerate() {
+        if included.option_groups.iter().any(|g| g.name == group.name) {
+            problems.push(ValidationProblem::new(
+                format!("option_groups[{}].name", i),
+                format!(
+                    "this replaces the option group named \"{}\" in an included patch set",
+                    group.name
+                ),
+            ));
+        }
+    }
+}
+
+/// Checks a patch set file for problems. Every problem that can be found is returned, so an empty
+/// list means the patch set can be loaded and its entries applied.
+pub fn validate(patch_set: &Value) -> Vec<ValidationProblem> {
+    let patch_set = match schema::migrate(patch_set.clone()) {
+        Ok(p) => p,
+        Err(e) => return vec![ValidationProblem::new("xbpatchset_schema", e)],
+    };
+
+    let problems = check_hex_strings(&patch_set);
+
+    // Anything else that stops the patch set from loading is only reported one problem at a time
+    if !problems.is_empty() {
+        return problems;
+    }
+
+    // Deserialized without going through the migration again, so that the path can be tracked
+    let mut track = serde_path_to_error::Track::new();
+
+    match PatchSet::deserialize(serde_path_to_error::Deserializer::new(
+        patch_set, &mut track,
+    )) {
+        Ok(patch_set) => check_patch_set(&patch_set),
+        Err(e) => vec![ValidationProblem::new(
+            track.path().to_string(),
+            e.to_string(),
+        )],
+    }
+}
+
+// Hex strings are checked by hand, so that every bad one is reported rather than just the first
+fn check_hex_strings(patch_set: &Value) -> Vec<ValidationProblem> {
+    let mut problems = Vec::new();
+
+    let mut check = |path: String, value: Option<&Value>, is_bytes: bool| {
+        let Some(value) = value.filter(|v| v.is_string()) else {
+            return;
+        };
+
+        let error = if is_bytes {
+            de_hex_to_vu8(value.clone()).err()
+        } else {
+            de_hex_to_u32(value.clone()).err()
+        };
+
+        if let Some(e) = error {
+            problems.push(ValidationProblem::new(
+                path,
+                format!("{} is not a valid hex string ({})", value, e),
+            ));
+        }
+    };
+
+    for (i, entry) in array(patch_set.get("entries")).iter().enumerate() {
+        let mut lists = vec![(format!("entries[{}]", i), entry)];
+
+        for (k, variant) in array(entry.get("variants")).iter().enumerate() {
+            lists.push((format!("entries[{}].variants[{}]", i, k), variant));
+        }
+
+        for (list_path, list) in lists {
+            for (j, patch) in array(list.get("patches")).iter().enumerate() {
+                let path = format!("{}.patches[{}]", list_path, j);
+
+                for field in ["offset", "jump_to", "call_to", "pointer_to", "copy_from"] {
+                    check(format!("{}.{}", path, field), patch.get(field), false);
+                }
+                for field in ["replacement_bytes", "fill", "original_bytes"] {
+                    check(format!("{}.{}", path, field), patch.get(field), true);
+                }
+            }
+        }
+    }
+
+    for (i, symbol) in array(patch_set.get("symbols")).iter().enumerate() {
+        let path = format!("symbols[{}]", i);
+
+        check(format!("{}.address", path), symbol.get("address"), false);
+        for (j, revision) in array(symbol.get("revisions")).iter().enumerate() {
+            check(
+                format!("{}.revisions[{}].address", path, j),
+                revision.get("address"),
+                false,
+            );
+        }
+    }
+
+    problems
+}
+
+fn array(value: Option<&Value>) -> &[Value] {
+    value
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+/// Checks the parts of a patch set that refer to each other. References to entries and symbols
+/// are only checked if the patch set doesn't include others, since they could be in those.
+pub fn check_patch_set(patch_set: &PatchSet) -> Vec<ValidationProblem> {
+    let available = patch_set.includes.is_empty().then_some(patch_set);
+    check(patch_set, available)
+}
+
+// References are checked against the entries and symbols of `available`, if it is given
+fn check(patch_set: &PatchSet, available: Option<&PatchSet>) -> Vec<ValidationProblem> {
+    let mut problems = Vec::new();
+
+    check_translation_keys(&mut problems, "", &patch_set.translations);
+
+    let mut entry_names = HashSet::new();
+
+    for (i, entry) in patch_set.entries.iter().enumerate() {
+        let path = format!("entries[{}]", i);
+
+        if !entry_names.insert(entry.name()) {
+            problems.push(ValidationProblem::new(
+                format!("{}.name", path),
+                format!("another entry is already named \"{}\"", entry.name()),
+            ));
+        }
+
+        if entry.get_patches().is_empty() && entry.variants().is_empty() && entry.files().is_empty()
+        {
+            problems.push(ValidationProblem::new(
+                format!("{}.patches", path),
+                "the entry has no patches",
+            ));
+        }
+
+        for (field, references) in [
+            ("requires", entry.requires()),
+            ("conflicts", entry.conflicts()),
+        ] {
+            for (j, reference) in references.iter().enumerate() {
+                if let EntryReference::SameSet(name) = reference
+                    && let Some(available) = available
+                    && !available.entries.iter().any(|e| e.name() == name)
+                {
+                    problems.push(ValidationProblem::new(
+                        format!("{}.{}[{}]", path, field, j),
+                        format!("there is no entry named \"{}\"", name),
+                    ));
+                }
+            }
+        }
+
+        check_translation_keys(&mut problems, &path, entry.translations());
+
+        for (j, change) in entry.files().iter().enumerate() {
+            if let Err((field, reason)) = change.check() {
+                problems.push(ValidationProblem::new(
+                    format!("{}.files[{}].{}", path, j, field),
+                    reason,
+                ));
+            }
+        }
+
+        for (j, parameter) in entry.parameters().iter().enumerate() {
+            if let Err(e) = parameter.encode(parameter.default) {
+                problems.push(ValidationProblem::new(
+                    format!("{}.parameters[{}].default", path, j),
+                    e,
+                ));
+            }
+        }
+
+        let mut lists = vec![(path.clone(), entry.get_patches())];
+
+        for (k, variant) in entry.variants().iter().enumerate() {
+            let variant_path = format!("{}.variants[{}]", path, k);
+
+            check_sha1(&mut problems, &variant_path, variant.build.sha1.as_deref());
+            lists.push((variant_path, &variant.patches));
+        }
+
+        for (list_path, patches) in lists {
+            for (j, patch) in patches.iter().enumerate() {
+                let path = format!("{}.patches[{}]", list_path, j);
+
+                if let PatchContents::Parameter { parameter } = &patch.contents
+                    && entry.parameter(parameter).is_none()
+                {
+                    problems.push(ValidationProblem::new(
+                        format!("{}.parameter", path),
+                        format!("the entry has no parameter named \"{}\"", parameter),
+                    ));
+                }
+
+                match &patch.contents {
+                    PatchContents::Fill { fill, .. } if fill.is_empty() => {
+                        problems.push(ValidationProblem::new(
+                            format!("{}.fill", path),
+                            "the pattern to fill with is empty",
+                        ));
+                    }
+                    PatchContents::Fill { length: 0, .. }
+                    | PatchContents::Copy { length: 0, .. } => {
+                        problems.push(ValidationProblem::new(
+                            format!("{}.length", path),
+                            "the patch writes no bytes",
+                        ));
+                    }
+                    PatchContents::Fill { length, .. } | PatchContents::Copy { length, .. }
+                        if *length > operation::MAX_LENGTH =>
+                    {
+                        problems.push(ValidationProblem::new(
+                            format!("{}.length", path),
+                            format!(
+                                "the patch writes {} bytes, but can write at most {}",
+                                length,
+                                operation::MAX_LENGTH
+                            ),
+                        ));
+                    }
+                    PatchContents::Text {
+                        text,
+                        encoding,
+                        padding,
+                        max_length,
+                    } => {
+                        let fits = match text::available_length(patch, *max_length) {
+                            Some(available) => {
+                                text::encode_to_fit(text, *encoding, *padding, available)
+                            }
+                            None => encoding.encode(text),
+                        };
+
+                        if let Err(e) = fits {
+                            problems.push(ValidationProblem::new(format!("{}.text", path), e));
+                        }
+                    }
+                    PatchContents::Assembly { max_size: None, .. }
+                        if patch.original_bytes.is_none() =>
+                    {
+                        problems.push(ValidationProblem::new(
+                            format!("{}.max_size", path),
+                            "assembly patches need a max_size or original_bytes, so they can't write over the code after them",
+                        ));
+                    }
+                    PatchContents::Random { random } => {
+                        if let Err(e) = random.check() {
+                            problems.push(ValidationProblem::new(format!("{}.random", path), e));
+                        }
+                    }
+                    _ => (),
+                }
+
+                if let PatchOffsetType::Text { text, encoding, .. } = &patch.offset_type {
+                    let problem = match encoding.encode(text) {
+                        Ok(bytes) if bytes.is_empty() => {
+                            Some("the text to search for is empty".to_string())
+                        }
+                        Ok(_) => None,
+                        Err(e) => Some(e),
+                    };
+
+                    if let Some(problem) = problem {
+                        problems.push(ValidationProblem::new(
+                            format!("{}.offset_type", path),
+                            problem,
+                        ));
+                    }
+                }
+
+                if let Some(file) = &patch.file
+                    && !is_game_file_path(file)
+                {
+                    problems.push(ValidationProblem::new(
+                        format!("{}.file", path),
+                        format!("\"{}\" is not a path inside the game's folder", file),
+                    ));
+                }
+
+                if let PatchOffsetType::Symbol(name) = &patch.offset_type
+                    && let Some(available) = available
+                    && available.symbol(name).is_none()
+                {
+                    problems.push(ValidationProblem::new(
+                        format!("{}.offset_type", path),
+                        format!("the patch set has no symbol named \"{}\"", name),
+                    ));
+                }
+            }
+        }
+    }
+
+    for (i, target) in patch_set.targets.iter().enumerate() {
+        check_sha1(
+            &mut problems,
+            &format!("targets[{}]", i),
+            target.build.sha1.as_deref(),
+        );
+    }
+
+    let mut category_names = HashSet::new();
+
+    for (i, category) in patch_set.categories.iter().enumerate() {
+        if !category_names.insert(category) {
+            problems.push(ValidationProblem::new(
+                format!("categories[{}]", i),
+                format!("\"{}\" is already listed", category),
+            ));
+        }
+    }
+
+    let mut symbol_names = HashSet::new();
+
+    for (i, symbol) in patch_set.symbols.iter().enumerate() {
+        if !symbol_names.insert(&symbol.name) {
+            problems.push(ValidationProblem::new(
+                format!("symbols[{}].name", i),
+                format!("another symbol is already named \"{}\"", symbol.name),
+            ));
+        }
+    }
+
+    problems
+}
+
+// Builds are matched by comparing hashes, so a mistyped one would never match
+fn check_sha1(problems: &mut Vec<ValidationProblem>, path: &str, sha1: Option<&str>) {
+    if let Some(sha1) = sha1
+        && (sha1.len() != 40 || !sha1.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        problems.push(ValidationProblem::new(
+            format!("{}.sha1", path),
+            format!("\"{}\" is not a SHA-1 hash of 40 hex digits", sha1),
+        ));
+    }
+}
+
+fn check_translation_keys(
+    problems: &mut Vec<ValidationProblem>,
+    path: &str,
+    translations: &Translations,
+) {
+    let prefix = if path.is_empty() {
+        String::new()
+    } else {
+        format!("{}.", path)
+    };
+
+    for code in translations.keys() {
+        if !translation::is_language_code(code) {
+            problems.push(ValidationProblem::new(
+                format!("{}translations.{}", prefix, code),
+                format!(
+                    "\"{}\" is not a language code like \"ja\" or \"pt-BR\"",
+                    code
+                ),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        patching::validation::{ValidationProblem, validate, validate_file},
+        test_util::{patch_set_json, temp_folder},
+    };
+
+    #[test]
+    fn reports_every_bad_hex_string() {
+        let patch_set = patch_set_json(
+            "ghoulies",
+            serde_json::json!([
+                {
+                    "name": "Disable reaper spawn",
+                    "description": "",
+                    "alt_author": null,
+                    "patches": [
+                        { "offset": "0xd0d50", "offset_type": "Virtual", "replacement_bytes": "0x909" },
+                        { "offset": "0xd0dzz", "offset_type": "Virtual", "replacement_bytes": "0x90" }
+                    ]
+                }
+            ]),
+        );
+
+        let paths: Vec<String> = validate(&patch_set).into_iter().map(|p| p.path).collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                "entries[0].patches[0].replacement_bytes",
+                "entries[0].patches[1].offset"
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_problems_between_entries() {
+        let patch_set = patch_set_json(
+            "ghoulies",
+            serde_json::json!([
+                {
+                    "name": "Force 1sec cutscenes",
+                    "description": "",
+                    "alt_author": null,
+                    "requires": ["Missing"],
+                    "patches": [
+                        { "offset": "0x4d5ac", "offset_type": "Virtual", "parameter": "Length" }
+                    ]
+                },
+                {
+                    "name": "Force 1sec cutscenes",
+                    "description": "",
+                    "alt_author": null,
+                    "patches": []
+                }
+            ]),
+        );
+
+        assert_eq!(
+            validate(&patch_set),
+            vec![
+                ValidationProblem::new(
+                    "entries[0].requires[0]",
+                    "there is no entry named \"Missing\""
+                ),
+                ValidationProblem::new(
+                    "entries[0].patches[0].parameter",
+                    "the entry has no parameter named \"Length\""
+                ),
+                ValidationProblem::new(
+                    "entries[1].name",
+                    "another entry is already named \"Force 1sec cutscenes\""
+                ),
+                ValidationProblem::new("entries[1].patches", "the entry has no patches"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_bad_file_changes() {
+        let patch_set = patch_set_json(
+            "ghoulies",
+            serde_json::json!([
+                {
+                    "name": "Title music swap",
+                    "description": "",
+                    "alt_author": null,
+                    "files": [
+                        { "path": "media/music/title.wma", "action": "Replace", "source": "title.wma" },
+                        { "path": "../title.wma", "action": "Delete" },
+                        { "path": "media/music/extra.wma", "action": "Add" }
+                    ]
+                }
+            ]),
+        );
+
+        let paths: Vec<String> = validate(&patch_set).into_iter().map(|p| p.path).collect();
+
+        assert_eq!(
+            paths,
+            vec!["entries[0].files[1].path", "entries[0].files[2].source"]
+        );
+    }
+
+    #[test]
+    fn reports_text_that_doesnt_fit() {
+        let patch_set = patch_set_json(
+            "ghoulies",
+            serde_json::json!([
+                {
+                    "name": "Rename the mansion",
+                    "description": "",
+                    "alt_author": null,
+                    "patches": [
+                        { "offset": "0x0", "offset_type": { "Text": { "text": "Mansion" } }, "text": "Manor" },
+                        { "offset": "0x0", "offset_type": { "Text": { "text": "Mansion" } }, "text": "Haunted Mansion" },
+                        { "offset": "0x2f10", "offset_type": "Raw", "text": "Château", "max_length": 16 },
+                        { "offset": "0x0", "offset_type": { "Text": { "text": "" } }, "text": "Hall" }
+                    ]
+                }
+            ]),
+        );
+
+        let paths: Vec<String> = validate(&patch_set).into_iter().map(|p| p.path).collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                "entries[0].patches[1].text",
+                "entries[0].patches[2].text",
+                "entries[0].patches[3].text",
+                "entries[0].patches[3].offset_type"
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_random_values_that_cant_be_picked() {
+        let patch_set = patch_set_json(
+            "ghoulies",
+            serde_json::json!([
+                {
+                    "name": "Random replay scene",
+                    "description": "",
+                    "alt_author": null,
+                    "patches": [
+                        { "offset": "0x1000", "offset_type": "Raw", "random": { "choices": [] } },
+                        { "offset": "0x1000", "offset_type": "Raw", "random": { "min": 1, "max": 300, "value_type": "U8" } },
+                        { "offset": "0x1000", "offset_type": "Raw", "random": { "min": 0.5, "max": 0.8, "value_type": "F32" } }
+                    ]
+                }
+            ]),
+        );
+
+        let paths: Vec<String> = validate(&patch_set).into_iter().map(|p| p.path).collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                "entries[0].patches[0].random",
+                "entries[0].patches[1].random"
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_lengths_that_are_too_long() {
+        let patch_set = patch_set_json(
+            "ghoulies",
+            serde_json::json!([
+                {
+                    "name": "Nop out the intro",
+                    "description": "",
+                    "alt_author": null,
+                    "patches": [
+                        { "offset": "0x1000", "offset_type": "Raw", "fill": "0x90", "length": 16 },
+                        { "offset": "0x1000", "offset_type": "Raw", "fill": "0x90", "length": 0x7fffffff },
+                        { "offset": "0x1000", "offset_type": "Raw", "copy_from": "0x11000", "length": 0x7fffffff }
+                    ]
+                }
+            ]),
+        );
+
+        let paths: Vec<String> = validate(&patch_set).into_iter().map(|p| p.path).collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                "entries[0].patches[1].length",
+                "entries[0].patches[2].length"
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_assembly_without_a_size() {
+        let patch_set = patch_set_json(
+            "ghoulies",
+            serde_json::json!([
+                {
+                    "name": "Randomise replay scene",
+                    "description": "",
+                    "alt_author": null,
+                    "patches": [
+                        { "offset": "0xe4e59", "offset_type": "Virtual", "assembly": ["nop"], "max_size": 1 },
+                        { "offset": "0xe4e59", "offset_type": "Virtual", "assembly": ["nop"], "original_bytes": "0x90" },
+                        { "offset": "0xe4e59", "offset_type": "Virtual", "assembly": ["nop"] }
+                    ]
+                }
+            ]),
+        );
+
+        let paths: Vec<String> = validate(&patch_set).into_iter().map(|p| p.path).collect();
+
+        assert_eq!(paths, vec!["entries[0].patches[2].max_size"]);
+    }
+
+    #[test]
+    fn reports_bad_variant_hashes() {
+        let patch_set = patch_set_json(
+            "ghoulies",
+            serde_json::json!([
+                {
+                    "name": "Disable reaper spawn",
+                    "description": "",
+                    "alt_author": null,
+                    "variants": [
+                        {
+                            "sha1": "7e5b2ff1c6b1f3e1e6a7b2b0f3f0c2a9e4d1b7c3",
+                            "patches": [
+                                { "offset": "0xd0e10", "offset_type": "Virtual", "replacement_bytes": "0x90" }
+                            ]
+                        },
+                        {
+                            "sha1": "7e5b2ff1",
+                            "patches": [
+                                { "offset": "0xd0e10", "offset_type": "Virtual", "replacement_bytes": "0x90" }
+                            ]
+                        }
+                    ]
+                }
+            ]),
+        );
+
+        let paths: Vec<String> = validate(&patch_set).into_iter().map(|p| p.path).collect();
+
+        assert_eq!(paths, vec!["entries[0].variants[1].sha1"]);
+    }
+
+    #[test]
+    fn reports_parameter_defaults_out_of_range() {
+        let patch_set = patch_set_json(
+            "ghoulies",
+            serde_json::json!([
+                {
+                    "name": "Frame cap",
+                    "description": "",
+                    "alt_author": null,
+                    "parameters": [
+                        { "name": "fps", "value_type": "U8", "default": 60.0, "min": 30.0, "max": 120.0 },
+                        { "name": "slow fps", "value_type": "U8", "default": 20.0, "min": 30.0 },
+                        { "name": "fast fps", "value_type": "U8", "default": 300.0 }
+                    ],
+                    "patches": [
+                        { "offset": "0x4fc10", "offset_type": "Virtual", "parameter": "fps" }
+                    ]
+                }
+            ]),
+        );
+
+        let paths: Vec<String> = validate(&patch_set).into_iter().map(|p| p.path).collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                "entries[0].parameters[1].default",
+                "entries[0].parameters[2].default"
+            ]
+        );
+    }
+
+    #[test]
+    fn explains_bad_patch_contents() {
+        let problem = |patch: serde_json::Value| {
+            let patch_set = patch_set_json(
+                "ghoulies",
+                serde_json::json!([
+                    {
+                        "name": "Frame cap",
+                        "description": "",
+                        "alt_author": null,
+                        "patches": [patch]
+                    }
+                ]),
+            );
+
+            let problems = validate(&patch_set);
+            assert_eq!(problems.len(), 1);
+            problems[0].message.clone()
+        };
+
+        let mixed = problem(serde_json::json!({
+            "offset": "0x4fc10", "offset_type": "Virtual", "replacement_bytes": "0x90", "jump_to": "0x10000"
+        }));
+        assert!(
+            mixed.contains("only have one of replacement_bytes, jump_to"),
+            "{}",
+            mixed
+        );
+
+        let extra = problem(serde_json::json!({
+            "offset": "0x4fc10", "offset_type": "Virtual", "replacement_bytes": "0x90", "length": 4
+        }));
+        assert!(extra.contains("length can't be used"), "{}", extra);
+
+        let missing = problem(serde_json::json!({
+            "offset": "0x4fc10", "offset_type": "Virtual", "fill": "0x90"
+        }));
+        assert!(missing.contains("missing field `length`"), "{}", missing);
+
+        let wrong_type = problem(serde_json::json!({
+            "offset": "0x4fc10", "offset_type": "Virtual", "assembly": "nop", "max_size": 1
+        }));
+        assert!(
+            wrong_type.starts_with("assembly: invalid type"),
+            "{}",
+            wrong_type
+        );
+
+        let none = problem(serde_json::json!({ "offset": "0x4fc10", "offset_type": "Virtual" }));
+        assert!(none.contains("needs one of replacement_bytes"), "{}", none);
+    }
+
+    #[test]
+    fn reports_symbols_and_groups_that_replace_included_ones() {
+        let folder = temp_folder("reports_symbols_and_groups_that_replace_included_ones");
+
+        let mut base = patch_set_json("engine base", serde_json::json!([]));
+        base["symbols"] = serde_json::json!([
+            { "name": "frame_cap", "address": "0x4fc10" },
+            { "name": "fov", "address": "0x4fc20" }
+        ]);
+        base["option_groups"] = serde_json::json!([{ "name": "Aspect ratio" }]);
+        std::fs::write(folder.join("base.json"), base.to_string()).unwrap();
+
+        let mut ghoulies = patch_set_json("ghoulies", serde_json::json!([]));
+        ghoulies["includes"] = serde_json::json!([{ "path": "base.json" }]);
+        ghoulies["symbols"] = serde_json::json!([
+            { "name": "health", "address": "0x5a000" },
+            { "name": "fov", "address": "0x4fd20" }
+        ]);
+        ghoulies["option_groups"] = serde_json::json!([{ "name": "Aspect ratio" }]);
+
+        let path = folder.join("ghoulies.json");
+        std::fs::write(&path, ghoulies.to_string()).unwrap();
+
+        let paths: Vec<String> = validate_file(&path)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.path)
+            .collect();
+
+        assert_eq!(paths, vec!["symbols[1].name", "option_groups[0].name"]);
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn reports_where_loading_failed() {
+        let mut patch_set = patch_set_json("ghoulies", serde_json::json!([]));
+        patch_set["version_major"] = "one".into();
+
+        let problems = validate(&patch_set);
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].path, "version_major");
+
+        patch_set["xbpatchset_schema"] = 99.into();
+        assert_eq!(validate(&patch_set)[0].path, "xbpatchset_schema");
+    }
+}
@@ -0,0 +1,121 @@
+//! Helpers shared by the tests in this crate.
+
+use std::path::{Path, PathBuf};
+
+use crate::patching::PatchSet;
+
+pub const IMAGE_BASE: u32 = 0x10000;
+
+const SECTION_HEADERS: u32 = 0x200;
+const SECTION_NAMES: u32 = 0x400;
+const CERTIFICATE: u32 = 0x600;
+const HEADERS_SIZE: u32 = 0x1000;
+
+/// An empty folder in the system's temp folder, for tests that need to read and write files.
+pub fn temp_folder(name: &str) -> PathBuf {
+    let folder = std::env::temp_dir().join(format!("xbpatch_{}_{}", name, std::process::id()));
+
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+
+    folder
+}
+
+/// The bytes of a small XBE with the given sections, as (name, virtual address, contents). Each
+/// section starts on its own 0x1000 byte page in the file.
+pub fn test_xbe(sections: &[(&str, u32, &[u8])]) -> Vec<u8> {
+    let mut xbe = vec![0u8; HEADERS_SIZE as usize];
+    let put_u32 = |xbe: &mut Vec<u8>, at: u32, value: u32| {
+        xbe[at as usize..at as usize + 4].copy_from_slice(&value.to_le_bytes())
+    };
+
+    xbe[..4].copy_from_slice(b"XBEH");
+    put_u32(&mut xbe, 0x104, IMAGE_BASE);
+    put_u32(&mut xbe, 0x108, HEADERS_SIZE);
+    put_u32(&mut xbe, 0x118, IMAGE_BASE + CERTIFICATE);
+    put_u32(&mut xbe, 0x11c, sections.len() as u32);
+    put_u32(&mut xbe, 0x120, IMAGE_BASE + SECTION_HEADERS);
+
+    // Title ID, region and version
+    put_u32(&mut xbe, CERTIFICATE + 0x8, 0x4d530063);
+    put_u32(&mut xbe, CERTIFICATE + 0xa0, 0x1);
+    put_u32(&mut xbe, CERTIFICATE + 0xac, 0x1);
+
+    let mut name_at = SECTION_NAMES;
+
+    for (i, (name, virtual_address, contents)) in sections.iter().enumerate() {
+        let header = SECTION_HEADERS + i as u32 * 36;
+        let file_offset = xbe.len() as u32;
+
+        put_u32(&mut xbe, header + 0x4, *virtual_address);
+        put_u32(&mut xbe, header + 0x8, contents.len() as u32);
+        put_u32(&mut xbe, header + 0xc, file_offset);
+        put_u32(&mut xbe, header + 0x10, contents.len() as u32);
+        put_u32(&mut xbe, header + 0x14, IMAGE_BASE + name_at);
+
+        xbe[name_at as usize..name_at as usize + name.len()].copy_from_slice(name.as_bytes());
+        name_at += name.len() as u32 + 1;
+
+        xbe.extend_from_slice(contents);
+        xbe.resize(xbe.len().next_multiple_of(0x1000), 0);
+    }
+
+    xbe
+}
+
+/// An entry named `name`, with the given extra fields, eg. its patches.
+pub fn entry(name: &str, fields: serde_json::Value) -> serde_json::Value {
+    let mut entry = serde_json::json!({
+        "name": name,
+        "description": "",
+        "alt_author": null
+    });
+
+    if let serde_json::Value::Object(fields) = fields {
+        entry.as_object_mut().unwrap().extend(fields);
+    }
+
+    entry
+}
+
+/// A patch set file's JSON with the given entries.
+pub fn patch_set_json(name: &str, entries: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "xbpatchset_schema": 1,
+        "name": name,
+        "author": "hethan/rectangle",
+        "version_major": 1,
+        "version_minor": 0,
+        "game_title": "Grabbed by the Ghoulies",
+        "entries": entries
+    })
+}
+
+/// A loaded patch set with the given entries.
+pub fn patch_set(name: &str, entries: serde_json::Value) -> PatchSet {
+    serde_json::from_value(patch_set_json(name, entries)).unwrap()
+}
+
+/// Writes a patch set whose one entry replaces a music file with one from its assets folder, for
+/// tests of features that cover a set's files as well as its JSON.
+pub fn write_music_swap_patch_set(folder: &Path) -> PathBuf {
+    let patch_set = patch_set_json(
+        "ghoulies",
+        serde_json::json!([entry(
+            "Title music swap",
+            serde_json::json!({
+                "files": [
+                    { "path": "media/music/title.wma", "action": "Replace", "source": "assets/title.wma" }
+                ]
+            })
+        )]),
+    );
+
+    std::fs::create_dir_all(folder.join("assets")).unwrap();
+    std::fs::write(folder.join("assets/title.wma"), b"music").unwrap();
+
+    let path = folder.join("ghoulies.json");
+    std::fs::write(&path, patch_set.to_string()).unwrap();
+
+    path
+}
@@ -1,30 +1,82 @@
 use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Display,
     fs::{File, OpenOptions},
     io::{Read, Seek, SeekFrom, Write},
     path::PathBuf,
 };
 
+use sha1::{Digest, Sha1};
+
 use crate::{
-    HasPatches,
+    HasPatches, assembler,
     memory::MemoryMap,
-    patching::{Patch, PatchOffsetType},
+    patching::{
+        Patch, PatchContents, PatchEntry, PatchOffsetType, ResolvedPatch, operation,
+        overlap::{self, PatchOverlap},
+        signature::BytePattern,
+        text,
+    },
 };
 
+/// The details that identify which game, and which build of it, an XBE is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XBEIdentity {
+    pub title_id: u32,
+    pub version: u32,
+
+    // Flags for each region the game can be played in
+    pub region: u32,
+
+    // SHA-1 of the whole file, as lowercase hex
+    pub sha1: String,
+}
+
+impl Display for XBEIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "title ID {:08X}, version {}, region 0x{:X}, SHA-1 {}",
+            self.title_id, self.version, self.region, self.sha1
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct XBEWriter {
     xbe_file: File,
     xbe_header: XBEHeader,
     mem_map: MemoryMap,
+    searches: SearchCache,
 }
 
+// The file offsets each pattern was found at, keyed by the kind of search, the pattern and the
+// section searched. Patch sets often find several patches from the same signature, and searching
+// the whole XBE each time is slow. Later searches also find the bytes from before any patches were
+// written, like the searches made when checking for overlaps.
+type SearchCache = RefCell<HashMap<(&'static str, String, Option<String>), Vec<u64>>>;
+
 #[derive(Debug, Default)]
 pub struct PatchReport {
     successes: u32,
     failures: u32,
     total: u32,
+
+    // The seed random values were picked with, so the same build can be made again
+    seed: Option<u64>,
 }
 
 impl PatchReport {
+    #[inline]
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+    #[inline]
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
     #[inline]
     pub fn add_success(&mut self) {
         self.successes += 1;
@@ -74,17 +126,38 @@ impl XBEWriter {
             xbe_file,
             mem_map,
             xbe_header,
+            searches: SearchCache::default(),
         })
     }
 
+    pub fn identify(&self) -> Result<XBEIdentity, std::io::Error> {
+        identify(&self.xbe_file, &self.xbe_header)
+    }
+
+    /// Finds entries that would write over each other's bytes if they were all applied.
+    pub fn find_overlaps(
+        &self,
+        entries: &[PatchEntry],
+    ) -> Result<Vec<PatchOverlap>, std::io::Error> {
+        overlap::find_overlaps(entries, |p| self.resolve_patch(p))
+    }
+
+    /// Works out exactly which bytes a patch will write, and where in the file.
+    pub fn resolve_patch(&self, patch: &Patch) -> Result<ResolvedPatch, std::io::Error> {
+        resolve_patch(
+            &self.xbe_file,
+            &self.xbe_header,
+            &self.mem_map,
+            &self.searches,
+            patch,
+        )
+    }
+
     pub fn apply_patch(&mut self, patch: &Patch) -> Result<(), std::io::Error> {
-        let offset: u64 = match patch.offset_type {
-            PatchOffsetType::Raw => patch.offset.into(),
-            PatchOffsetType::Virtual => self.mem_map.get_raw_offset(patch.offset)?.into(),
-        };
+        let resolved = self.resolve_patch(patch)?;
 
-        self.xbe_file.seek(SeekFrom::Start(offset))?;
-        self.xbe_file.write_all(patch.replacement_bytes.as_ref())?;
+        self.xbe_file.seek(SeekFrom::Start(resolved.file_offset))?;
+        self.xbe_file.write_all(resolved.bytes.as_ref())?;
         Ok(())
     }
 
@@ -112,6 +185,406 @@ impl XBEWriter {
     }
 }
 
+/// Read-only access to an XBE, used when inspecting a clean copy of a game's executable.
+#[derive(Debug)]
+pub struct XBEReader {
+    xbe_file: File,
+    xbe_header: XBEHeader,
+    mem_map: MemoryMap,
+    searches: SearchCache,
+}
+
+impl XBEReader {
+    pub fn new(path: &PathBuf) -> Result<XBEReader, std::io::Error> {
+        let mut xbe_file = File::open(path)?;
+        let xbe_header = XBEHeader::from_file(&mut xbe_file)?;
+        let mem_map = MemoryMap::from_xbe_header(&xbe_header);
+
+        Ok(XBEReader {
+            xbe_file,
+            xbe_header,
+            mem_map,
+            searches: SearchCache::default(),
+        })
+    }
+
+    pub fn identify(&self) -> Result<XBEIdentity, std::io::Error> {
+        identify(&self.xbe_file, &self.xbe_header)
+    }
+
+    /// Works out exactly which bytes a patch would write, and where in the file.
+    pub fn resolve_patch(&self, patch: &Patch) -> Result<ResolvedPatch, std::io::Error> {
+        resolve_patch(
+            &self.xbe_file,
+            &self.xbe_header,
+            &self.mem_map,
+            &self.searches,
+            patch,
+        )
+    }
+
+    /// Reads the `len` bytes that a patch would overwrite.
+    pub fn read_original_bytes(
+        &mut self,
+        patch: &Patch,
+        len: usize,
+    ) -> Result<Vec<u8>, std::io::Error> {
+        let offset = get_file_offset(
+            &self.xbe_file,
+            &self.xbe_header,
+            &self.mem_map,
+            &self.searches,
+            patch,
+        )?;
+
+        let mut bytes = vec![0u8; len];
+        self.xbe_file.seek(SeekFrom::Start(offset))?;
+        self.xbe_file.read_exact(&mut bytes)?;
+
+        Ok(bytes)
+    }
+}
+
+fn identify(mut file: &File, header: &XBEHeader) -> Result<XBEIdentity, std::io::Error> {
+    let mut hasher = Sha1::new();
+
+    let mut buf = [0u8; 0x10000];
+    file.seek(SeekFrom::Start(0))?;
+
+    loop {
+        let read = file.read(&mut buf)?;
+
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..read]);
+    }
+
+    let sha1 = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    Ok(XBEIdentity {
+        title_id: header.title_id(),
+        version: header.version(),
+        region: header.region(),
+        sha1,
+    })
+}
+
+fn resolve_patch(
+    mut file: &File,
+    header: &XBEHeader,
+    mem_map: &MemoryMap,
+    searches: &SearchCache,
+    patch: &Patch,
+) -> Result<ResolvedPatch, std::io::Error> {
+    let file_offset = get_file_offset(file, header, mem_map, searches, patch)?;
+
+    let bytes = match &patch.contents {
+        PatchContents::Bytes { replacement_bytes } => replacement_bytes.clone(),
+        PatchContents::Parameter { parameter } => {
+            return Err(std::io::Error::other(format!(
+                "The value of parameter \"{}\" has not been chosen.",
+                parameter
+            )));
+        }
+        PatchContents::Random { .. } => {
+            return Err(std::io::Error::other(
+                "The random value has not been picked.",
+            ));
+        }
+        PatchContents::Assembly { assembly, max_size } => {
+            let address = virtual_address(mem_map, patch, file_offset)?;
+
+            let bytes = assembler::assemble(assembly, address).map_err(|e| {
+                std::io::Error::other(format!(
+                    "Unable to assemble the patch at 0x{:X}. {}",
+                    patch.offset, e
+                ))
+            })?;
+
+            let allowed = max_size
+                .or(patch.original_bytes.as_ref().map(|b| b.len()))
+                .ok_or(std::io::Error::other(format!(
+                    "The assembly patch at 0x{:X} needs a max_size or original_bytes.",
+                    patch.offset
+                )))?;
+
+            if bytes.len() > allowed {
+                return Err(std::io::Error::other(format!(
+                    "The patch at 0x{:X} assembles to {} bytes, but only {} are allowed.",
+                    patch.offset,
+                    bytes.len(),
+                    allowed
+                )));
+            }
+
+            bytes
+        }
+        PatchContents::Jump { jump_to } => {
+            operation::jump(virtual_address(mem_map, patch, file_offset)?, *jump_to)
+        }
+        PatchContents::Call { call_to } => {
+            operation::call(virtual_address(mem_map, patch, file_offset)?, *call_to)
+        }
+        PatchContents::Fill { fill, length } => operation::fill(fill, *length).map_err(|e| {
+            std::io::Error::other(format!("Unable to fill at 0x{:X}: {}.", patch.offset, e))
+        })?,
+        PatchContents::Copy { length: 0, .. } => {
+            return Err(std::io::Error::other(format!(
+                "The copy at 0x{:X} has a length of 0, so it writes nothing.",
+                patch.offset
+            )));
+        }
+        PatchContents::Copy { copy_from, length } => {
+            // The bytes must all come from the part of one section that is stored in the file,
+            // which also stops a huge length from being allocated
+            let start = u64::from(*copy_from);
+            let end = start.saturating_add(*length as u64);
+
+            let section = header
+                .sections()
+                .iter()
+                .find(|s| {
+                    let section_start = u64::from(s.virtual_offset);
+                    section_start <= start && end <= section_start + u64::from(s.file_size)
+                })
+                .ok_or(std::io::Error::other(format!(
+                    "The {} bytes copied from 0x{:X} aren't all in one section of the XBE.",
+                    length, copy_from
+                )))?;
+
+            let mut bytes = vec![0u8; *length];
+
+            file.seek(SeekFrom::Start(
+                u64::from(section.file_offset) + start - u64::from(section.virtual_offset),
+            ))?;
+            file.read_exact(&mut bytes)?;
+
+            bytes
+        }
+        PatchContents::Text {
+            text,
+            encoding,
+            padding,
+            max_length,
+        } => text::text_patch_bytes(patch, text, *encoding, *padding, *max_length)?,
+        contents => contents.fixed_bytes().ok_or(std::io::Error::other(format!(
+            "Unable to work out the bytes of the patch at 0x{:X}.",
+            patch.offset
+        )))?,
+    };
+
+    Ok(ResolvedPatch {
+        file: None,
+        file_offset,
+        bytes,
+    })
+}
+
+// The address a patch is written to once the XBE is loaded into memory
+fn virtual_address(
+    mem_map: &MemoryMap,
+    patch: &Patch,
+    file_offset: u64,
+) -> Result<u32, std::io::Error> {
+    match patch.offset_type {
+        PatchOffsetType::Virtual => Ok(patch.offset),
+        _ => mem_map.get_virtual_address(file_offset as u32),
+    }
+}
+
+fn get_file_offset(
+    file: &File,
+    header: &XBEHeader,
+    mem_map: &MemoryMap,
+    searches: &SearchCache,
+    patch: &Patch,
+) -> Result<u64, std::io::Error> {
+    Ok(match &patch.offset_type {
+        PatchOffsetType::Raw => patch.offset.into(),
+        PatchOffsetType::Virtual => mem_map.get_raw_offset(patch.offset)?.into(),
+        PatchOffsetType::Signature {
+            pattern,
+            section,
+            displacement,
+        } => {
+            let found = find_signature(file, header, searches, pattern, section.as_deref())?;
+
+            found
+                .checked_add_signed((*displacement).into())
+                .ok_or(std::io::Error::other(format!(
+                    "The displacement {} from the pattern \"{}\" is before the start of the XBE.",
+                    displacement, pattern
+                )))?
+                + u64::from(patch.offset)
+        }
+        PatchOffsetType::Text {
+            text,
+            encoding,
+            section,
+        } => {
+            let pattern = encoding
+                .encode_terminated(text)
+                .map(BytePattern::exact)
+                .map_err(|e| {
+                    std::io::Error::other(format!("Unable to search for \"{}\": {}.", text, e))
+                })?;
+
+            // Only whole strings are matched, so "Manor" isn't found at the end of "Old Manor".
+            // UTF-16 strings start on an even offset, like the characters in them.
+            let terminator = encoding.terminator();
+            let is_string_start = |contents: &[u8], offset: u64, i: usize| {
+                offset.is_multiple_of(terminator.len() as u64)
+                    && (i == 0 || contents[..i].ends_with(terminator))
+            };
+
+            find_pattern(
+                file,
+                header,
+                searches,
+                "text",
+                &pattern,
+                section.as_deref(),
+                is_string_start,
+            )? + u64::from(patch.offset)
+        }
+        PatchOffsetType::Symbol(name) => {
+            return Err(std::io::Error::other(format!(
+                "The symbol \"{}\" has not been looked up in its patch set.",
+                name
+            )));
+        }
+        PatchOffsetType::ResolvedSymbol(symbol) => {
+            let address = symbol.address_for(header.version());
+
+            let address = address
+                .checked_add(patch.offset)
+                .ok_or(std::io::Error::other(format!(
+                    "The offset 0x{:X} from symbol \"{}\" at 0x{:X} is past the end of memory.",
+                    patch.offset, symbol.name, address
+                )))?;
+
+            mem_map.get_raw_offset(address)?.into()
+        }
+    })
+}
+
+/// Finds the file offset of the only place a pattern appears, searching one section or all of
+/// them. Fails if the pattern appears anywhere else, since the patch could land in the wrong place.
+fn find_signature(
+    file: &File,
+    header: &XBEHeader,
+    searches: &SearchCache,
+    pattern: &BytePattern,
+    section: Option<&str>,
+) -> Result<u64, std::io::Error> {
+    find_pattern(
+        file,
+        header,
+        searches,
+        "signature",
+        pattern,
+        section,
+        |_, _, _| true,
+    )
+}
+
+// Like find_signature, but only counts the places that `accept` is true for. It is given the
+// section's contents, the file offset of the match, and where in the section it is. Searches with
+// a different `accept` must use a different `kind`, since their matches are cached separately.
+fn find_pattern(
+    file: &File,
+    header: &XBEHeader,
+    searches: &SearchCache,
+    kind: &'static str,
+    pattern: &BytePattern,
+    section: Option<&str>,
+    accept: impl Fn(&[u8], u64, usize) -> bool,
+) -> Result<u64, std::io::Error> {
+    let key = (kind, pattern.to_string(), section.map(str::to_string));
+
+    let cached = searches.borrow().get(&key).cloned();
+
+    let matches = match cached {
+        Some(matches) => matches,
+        None => {
+            let matches = search(file, header, pattern, section, accept)?;
+            searches.borrow_mut().insert(key, matches.clone());
+            matches
+        }
+    };
+
+    let place = match section {
+        Some(name) => format!("section {}", name),
+        None => "the XBE".to_string(),
+    };
+
+    match matches.as_slice() {
+        [offset] => Ok(*offset),
+        [] => Err(std::io::Error::other(format!(
+            "The pattern \"{}\" was not found in {}.",
+            pattern, place
+        ))),
+        _ => Err(std::io::Error::other(format!(
+            "The pattern \"{}\" was found {} times in {} (at file offsets {}), so it can't be used to find the patch.",
+            pattern,
+            matches.len(),
+            place,
+            matches
+                .iter()
+                .map(|m| format!("0x{:X}", m))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+// Every file offset in the searched sections that the pattern is found at
+fn search(
+    mut file: &File,
+    header: &XBEHeader,
+    pattern: &BytePattern,
+    section: Option<&str>,
+    accept: impl Fn(&[u8], u64, usize) -> bool,
+) -> Result<Vec<u64>, std::io::Error> {
+    let sections: Vec<&XBESectionHeader> = header
+        .sections()
+        .iter()
+        .filter(|s| section.is_none_or(|name| s.name == name))
+        .collect();
+
+    if sections.is_empty() {
+        return Err(std::io::Error::other(format!(
+            "The XBE has no section named \"{}\".",
+            section.unwrap_or_default()
+        )));
+    }
+
+    let mut matches = Vec::new();
+
+    for s in sections {
+        let mut contents = vec![0u8; s.file_size as usize];
+        file.seek(SeekFrom::Start(s.file_offset.into()))?;
+        file.read_exact(&mut contents)?;
+
+        matches.extend(
+            pattern
+                .find_all(&contents)
+                .into_iter()
+                .map(|i| (u64::from(s.file_offset) + i as u64, i))
+                .filter(|(offset, i)| accept(&contents, *offset, *i))
+                .map(|(offset, _)| offset),
+        );
+    }
+
+    Ok(matches)
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct XBESectionHeader {
     pub flags: u32,
@@ -123,6 +596,9 @@ pub struct XBESectionHeader {
     pub reference: u32,
     pub head_ref_ptr: u32,
     pub tail_ref_ptr: u32,
+
+    // Read from the string that name_ptr points to, eg. ".text"
+    pub name: String,
 }
 
 #[derive(Debug)]
@@ -135,6 +611,11 @@ pub struct XBEHeader {
     section_header_ptr: u32,
 
     sections: Vec<XBESectionHeader>,
+
+    // From the certificate, which identifies the game and which build of it this is
+    title_id: u32,
+    region: u32,
+    version: u32,
 }
 
 impl XBEHeader {
@@ -146,6 +627,18 @@ impl XBEHeader {
         self.section_count
     }
 
+    pub fn title_id(&self) -> u32 {
+        self.title_id
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn region(&self) -> u32 {
+        self.region
+    }
+
     pub fn from_file(file: &mut File) -> Result<XBEHeader, std::io::Error> {
         file.seek(SeekFrom::Start(0x104))?;
 
@@ -154,7 +647,10 @@ impl XBEHeader {
         file.read_exact(&mut buf_u32)?;
         let image_base = u32::from_le_bytes(buf_u32);
 
-        file.seek(SeekFrom::Start(0x11c))?;
+        file.seek(SeekFrom::Start(0x118))?;
+        file.read_exact(&mut buf_u32)?;
+        let certificate_ptr = u32::from_le_bytes(buf_u32);
+
         file.read_exact(&mut buf_u32)?;
         let section_count = u32::from_le_bytes(buf_u32);
 
@@ -164,7 +660,9 @@ impl XBEHeader {
         let mut sections = Vec::new();
         sections.resize(section_count as usize, XBESectionHeader::default());
 
-        file.seek(SeekFrom::Start((section_header_ptr - image_base).into()))?;
+        file.seek(SeekFrom::Start(
+            header_offset(section_header_ptr, image_base, "section headers")?.into(),
+        ))?;
         for i in 0..section_count as usize {
             file.read_exact(&mut buf_u32)?;
             sections[i].flags = u32::from_le_bytes(buf_u32);
@@ -194,11 +692,249 @@ impl XBEHeader {
             sections[i].tail_ref_ptr = u32::from_le_bytes(buf_u32);
         }
 
+        // Section names are stored in the headers, which are loaded at the image base
+        for section in &mut sections {
+            if let Some(name_offset) = section.name_ptr.checked_sub(image_base) {
+                file.seek(SeekFrom::Start(name_offset.into()))?;
+                section.name = read_c_string(file)?;
+            }
+        }
+
+        let certificate_offset =
+            u64::from(header_offset(certificate_ptr, image_base, "certificate")?);
+
+        file.seek(SeekFrom::Start(certificate_offset + 0x8))?;
+        file.read_exact(&mut buf_u32)?;
+        let title_id = u32::from_le_bytes(buf_u32);
+
+        file.seek(SeekFrom::Start(certificate_offset + 0xa0))?;
+        file.read_exact(&mut buf_u32)?;
+        let region = u32::from_le_bytes(buf_u32);
+
+        file.seek(SeekFrom::Start(certificate_offset + 0xac))?;
+        file.read_exact(&mut buf_u32)?;
+        let version = u32::from_le_bytes(buf_u32);
+
         Ok(XBEHeader {
             image_base,
             section_count,
             section_header_ptr,
             sections,
+            title_id,
+            region,
+            version,
         })
     }
 }
+
+// Pointers in the headers are addresses once the XBE is loaded at the image base
+fn header_offset(pointer: u32, image_base: u32, name: &str) -> Result<u32, std::io::Error> {
+    pointer
+        .checked_sub(image_base)
+        .ok_or(std::io::Error::other(format!(
+            "The XBE's {} at 0x{:X} are before its image base 0x{:X}.",
+            name, pointer, image_base
+        )))
+}
+
+fn read_c_string(file: &mut File) -> Result<String, std::io::Error> {
+    let mut bytes = Vec::new();
+    let mut byte = [0u8; 1];
+
+    loop {
+        file.read_exact(&mut byte)?;
+
+        if byte[0] == 0 {
+            break;
+        }
+
+        bytes.push(byte[0]);
+    }
+
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        patching::{Patch, PatchOffsetType, symbol::Symbol},
+        test_util::{temp_folder, test_xbe},
+        xbe::XBEReader,
+    };
+
+    fn patch(patch: serde_json::Value) -> Patch {
+        serde_json::from_value(patch).unwrap()
+    }
+
+    #[test]
+    fn bad_pointers_are_errors() {
+        let folder = temp_folder("xbe_pointers");
+        let path = folder.join("default.xbe");
+
+        let mut xbe = test_xbe(&[(".text", 0x11000, &[0x90; 0x10])]);
+        xbe[0x118..0x11c].copy_from_slice(&0x100u32.to_le_bytes());
+        std::fs::write(&path, xbe).unwrap();
+
+        assert!(XBEReader::new(&path).is_err());
+
+        std::fs::write(&path, test_xbe(&[(".text", 0x11000, &[0x90; 0x10])])).unwrap();
+        let reader = XBEReader::new(&path).unwrap();
+
+        let mut past_the_end = patch(serde_json::json!({
+            "offset": "0xffffffff", "offset_type": "Raw", "replacement_bytes": "0x90"
+        }));
+        past_the_end.offset_type = PatchOffsetType::ResolvedSymbol(Symbol {
+            name: "Main".to_string(),
+            address: 0x11000,
+            revisions: Vec::new(),
+        });
+        assert!(reader.resolve_patch(&past_the_end).is_err());
+    }
+
+    #[test]
+    fn signatures_are_found_once() {
+        let folder = temp_folder("xbe_signature");
+        let path = folder.join("default.xbe");
+
+        let text = [0x90, 0x90, 0x8b, 0x45, 0x08, 0x85, 0xc0, 0x74, 0x05, 0xc3];
+        let data = [0x85, 0xc0, 0x74, 0x05];
+        std::fs::write(
+            &path,
+            test_xbe(&[(".text", 0x11000, &text), (".data", 0x12000, &data)]),
+        )
+        .unwrap();
+
+        let reader = XBEReader::new(&path).unwrap();
+        let offset_of = |offset_type: serde_json::Value| {
+            reader.resolve_patch(&patch(serde_json::json!({
+                "offset": "0x1", "offset_type": offset_type, "replacement_bytes": "0xeb"
+            })))
+        };
+
+        let found = offset_of(serde_json::json!({
+            "Signature": { "pattern": "8B 45 ?? 85 C0 74 ??" }
+        }));
+        assert_eq!(found.unwrap().file_offset, 0x1000 + 2 + 1);
+
+        // A displacement moves the patch before the pattern
+        let before = offset_of(serde_json::json!({
+            "Signature": { "pattern": "8B 45 ?? 85 C0", "displacement": -3 }
+        }));
+        assert_eq!(before.unwrap().file_offset, 0x1000);
+
+        // The same pattern is found again from the cache
+        let before = offset_of(serde_json::json!({
+            "Signature": { "pattern": "8B 45 ?? 85 C0", "displacement": -2 }
+        }));
+        assert_eq!(before.unwrap().file_offset, 0x1000 + 1);
+
+        // Found in both sections, unless the search is limited to one
+        let pattern = "85 C0 74 05";
+        assert!(offset_of(serde_json::json!({ "Signature": { "pattern": pattern } })).is_err());
+        assert_eq!(
+            offset_of(serde_json::json!({
+                "Signature": { "pattern": pattern, "section": ".data" }
+            }))
+            .unwrap()
+            .file_offset,
+            0x2000 + 1
+        );
+
+        assert!(offset_of(serde_json::json!({ "Signature": { "pattern": "C3 C3" } })).is_err());
+    }
+
+    #[test]
+    fn text_is_only_found_as_a_whole_string() {
+        let folder = temp_folder("xbe_text");
+        let path = folder.join("default.xbe");
+
+        let mut rdata = b"Old Manor\0Manor\0".to_vec();
+        // "Hall" in UTF-16, once starting on an odd offset and once on an even one
+        rdata.extend([0x00, b'H', 0, b'a', 0, b'l', 0, b'l', 0, 0, 0]);
+        rdata.extend([0x00, b'H', 0, b'a', 0, b'l', 0, b'l', 0, 0, 0, 0x00]);
+
+        std::fs::write(&path, test_xbe(&[(".rdata", 0x11000, &rdata)])).unwrap();
+        let reader = XBEReader::new(&path).unwrap();
+
+        let manor = patch(serde_json::json!({
+            "offset": "0x0", "offset_type": { "Text": { "text": "Manor" } }, "text": "Hall"
+        }));
+        assert_eq!(
+            reader.resolve_patch(&manor).unwrap().file_offset,
+            0x1000 + 10
+        );
+
+        let hall = patch(serde_json::json!({
+            "offset": "0x0",
+            "offset_type": { "Text": { "text": "Hall", "encoding": "Utf16Le" } },
+            "replacement_bytes": "0x4d"
+        }));
+        assert_eq!(
+            reader.resolve_patch(&hall).unwrap().file_offset,
+            0x1000 + 28
+        );
+
+        // The end of a string isn't a string of its own
+        let anor = patch(serde_json::json!({
+            "offset": "0x0", "offset_type": { "Text": { "text": "anor" } }, "text": "Hall"
+        }));
+        assert!(reader.resolve_patch(&anor).is_err());
+    }
+
+    #[test]
+    fn copies_stay_inside_one_section() {
+        let folder = temp_folder("xbe_copy");
+        let path = folder.join("default.xbe");
+        std::fs::write(
+            &path,
+            test_xbe(&[
+                (".text", 0x11000, &[0x11; 0x10]),
+                (".data", 0x12000, &[0x22; 0x10]),
+            ]),
+        )
+        .unwrap();
+
+        let reader = XBEReader::new(&path).unwrap();
+
+        let copy = patch(serde_json::json!({
+            "offset": "0x11000", "offset_type": "Virtual", "copy_from": "0x12008", "length": 8
+        }));
+        assert_eq!(reader.resolve_patch(&copy).unwrap().bytes, vec![0x22; 8]);
+
+        let past_the_end = patch(serde_json::json!({
+            "offset": "0x11000", "offset_type": "Virtual", "copy_from": "0x12008", "length": 9
+        }));
+        assert!(reader.resolve_patch(&past_the_end).is_err());
+
+        let huge = patch(serde_json::json!({
+            "offset": "0x11000", "offset_type": "Virtual", "copy_from": "0x11000", "length": usize::MAX
+        }));
+        assert!(reader.resolve_patch(&huge).is_err());
+
+        let nothing = patch(serde_json::json!({
+            "offset": "0x11000", "offset_type": "Virtual", "copy_from": "0x12008", "length": 0
+        }));
+        assert!(reader.resolve_patch(&nothing).is_err());
+    }
+
+    #[test]
+    fn fills_that_write_nothing_are_errors() {
+        let folder = temp_folder("xbe_fill");
+        let path = folder.join("default.xbe");
+        std::fs::write(&path, test_xbe(&[(".text", 0x11000, &[0x11; 0x10])])).unwrap();
+
+        let reader = XBEReader::new(&path).unwrap();
+
+        let nops = patch(serde_json::json!({
+            "offset": "0x11000", "offset_type": "Virtual", "fill": "0x90", "length": 4
+        }));
+        assert_eq!(reader.resolve_patch(&nops).unwrap().bytes, vec![0x90; 4]);
+
+        for (fill, length) in [("0x", 4), ("0x90", 0)] {
+            let nothing = patch(serde_json::json!({
+                "offset": "0x11000", "offset_type": "Virtual", "fill": fill, "length": length
+            }));
+            assert!(reader.resolve_patch(&nothing).is_err());
+        }
+    }
+}
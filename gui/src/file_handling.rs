@@ -1,34 +1,21 @@
-use std::{
-    fs::{File, OpenOptions},
-    io::{Seek, SeekFrom},
-    path::PathBuf,
+use std::path::{Path, PathBuf};
+
+use xbpatch_core::{
+    file_handling::LiveFile,
+    patching::{
+        PatchEntry, PatchSet, include, patch_set, schema,
+        selection::SetSelection,
+        signing::{self, Keyring, Trust},
+    },
 };
 
-use serde::{Deserialize, Serialize};
-use xbpatch_core::patching::PatchEntry;
-
-#[derive(Serialize, Deserialize, Clone, Default, Debug)]
-pub struct PatchSet {
-    pub xbpatchset_schema: u32,
-    pub name: String,
-    pub author: String,
-    pub version_major: u8,
-    pub version_minor: u8,
-    pub game_title: String,
-    pub entries: Vec<PatchEntry>,
-}
-
-impl PatchSet {
-    pub fn len(&self) -> usize {
-        self.entries.len()
-    }
-}
-
 pub struct LoadedPatchSet {
     file: LiveFile<PatchSet>,
     pub patch_set: PatchSet,
-    // Array of indices showing which patch entries are enabled
-    enabled_entries: Vec<bool>,
+    // Which patch entries are enabled, and the values of their parameters
+    selection: SetSelection,
+    // Whether the patch set was signed by a trusted author, as of when it was last loaded or saved
+    trust: Trust,
 }
 
 impl LoadedPatchSet {
@@ -37,21 +24,34 @@ impl LoadedPatchSet {
     }
 
     pub fn enabled_entries(&self) -> &Vec<bool> {
-        &self.enabled_entries
+        &self.selection.enabled
     }
 
     pub fn enabled_entries_mut(&mut self) -> &mut Vec<bool> {
-        &mut self.enabled_entries
+        &mut self.selection.enabled
+    }
+
+    pub fn selection(&self) -> &SetSelection {
+        &self.selection
+    }
+
+    pub fn selection_mut(&mut self) -> &mut SetSelection {
+        &mut self.selection
+    }
+
+    pub fn trust(&self) -> &Trust {
+        &self.trust
     }
 
-    pub fn existing(path: &PathBuf) -> Result<Self, std::io::Error> {
+    pub fn existing(path: &PathBuf, keyring: &Keyring) -> Result<Self, std::io::Error> {
         let file = LiveFile::<PatchSet>::from_existing(path)?;
-        let patch_set: PatchSet = file.data().clone();
+        let patch_set = Self::patch_set_of(&file)?;
 
         Ok(LoadedPatchSet {
             file,
-            enabled_entries: vec![false; patch_set.len()],
+            selection: SetSelection::new(&patch_set),
             patch_set,
+            trust: signing::verify_patch_set(path, keyring),
         })
     }
 
@@ -59,18 +59,29 @@ impl LoadedPatchSet {
         let mut file = LiveFile::<PatchSet>::from_new(path, PatchSet::default())?;
 
         file.update(|ps| {
+            ps.json_schema = Some(format!("./{}", schema::JSON_SCHEMA_FILE_NAME));
             ps.name = name;
         })?;
 
-        let patch_set = file.data().clone();
+        let patch_set = Self::patch_set_of(&file)?;
 
         Ok(LoadedPatchSet {
             file,
-            enabled_entries: vec![false; patch_set.len()],
+            selection: SetSelection::new(&patch_set),
             patch_set,
+            trust: Trust::Unsigned,
         })
     }
 
+    // The sources of file changes are found relative to the patch set's file. Included entries are
+    // only shown and applied, so they're never saved into the patch set's own file.
+    fn patch_set_of(file: &LiveFile<PatchSet>) -> Result<PatchSet, std::io::Error> {
+        let mut patch_set = file.data().clone();
+        patch_set.folder = file.path().parent().map(Path::to_path_buf);
+
+        include::resolve_includes(patch_set, file.path())
+    }
+
     pub fn filename_from_name(name: &str) -> String {
         let mut ret = name.to_lowercase().replace(' ', "_");
         ret.push_str(".json");
@@ -82,87 +93,21 @@ impl LoadedPatchSet {
         self.file.path()
     }
 
-    pub fn data(&self) -> &PatchSet {
-        &self.patch_set
-    }
-}
-
-pub struct LiveFile<T>
-where
-    T: Serialize + for<'de> Deserialize<'de>,
-{
-    file: File,
-    path: PathBuf,
-    backup_path: PathBuf,
-    data: T,
-}
-
-impl<T> LiveFile<T>
-where
-    T: Serialize + for<'de> Deserialize<'de>,
-{
-    pub fn from_new(path: &PathBuf, data: T) -> Result<Self, std::io::Error> {
-        let file = OpenOptions::new()
-            .create(true)
-            .truncate(true)
-            .read(true)
-            .write(true)
-            .open(path)?;
-
-        let mut backup_path = path.clone();
-        backup_path.set_extension("json.bak");
-
-        Ok(LiveFile {
-            file,
-            path: path.clone(),
-            backup_path,
-            data,
-        })
-    }
-
-    pub fn from_existing(path: &PathBuf) -> Result<Self, std::io::Error> {
-        let file = OpenOptions::new().read(true).write(true).open(path)?;
-
-        let data: T = serde_json::from_reader(&file)?;
-
-        let mut backup_path = path.clone();
-        backup_path.set_extension("json.bak");
-
-        Ok(LiveFile {
-            file,
-            path: path.clone(),
-            backup_path,
-            data,
-        })
+    /// Fills in the original bytes of every patch using a clean XBE, and saves the patch set. A
+    /// signed patch set no longer matches its signature once it has been changed.
+    pub fn fill_original_bytes(
+        &mut self,
+        xbe_path: &PathBuf,
+        keyring: &Keyring,
+    ) -> Result<usize, std::io::Error> {
+        let filled = patch_set::fill_original_bytes(&mut self.file, xbe_path)?;
+        self.patch_set = Self::patch_set_of(&self.file)?;
+        self.trust = signing::verify_patch_set(self.file.path(), keyring);
+
+        Ok(filled)
     }
 
-    pub fn save(&mut self) -> Result<(), std::io::Error> {
-        // Clear the file
-        self.file.seek(SeekFrom::Start(0))?;
-        self.file.set_len(0)?;
-
-        std::fs::copy(&self.path, &self.backup_path)?;
-        serde_json::to_writer_pretty(&self.file, &self.data)?;
-        std::fs::remove_file(&self.backup_path)?;
-
-        Ok(())
-    }
-
-    pub fn data(&self) -> &T {
-        &self.data
-    }
-
-    pub fn update<F>(&mut self, f: F) -> Result<(), std::io::Error>
-    where
-        F: FnOnce(&mut T),
-    {
-        f(&mut self.data);
-        self.save()?;
-
-        Ok(())
-    }
-
-    pub fn path(&self) -> &PathBuf {
-        &self.path
+    pub fn data(&self) -> &PatchSet {
+        &self.patch_set
     }
 }
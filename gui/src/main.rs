@@ -12,6 +12,17 @@ use eframe::egui::{self, Color32, Id, Modal, TextEdit};
 use egui_file::FileDialog;
 
 mod file_handling;
+use xbpatch_core::patching::{
+    EntryReference, PatchEntry, PatchSet,
+    bundle::{self, PatchBundle},
+    patch_set::{OptionGroup, OptionGroupSelection},
+    schema,
+    selection::{self, SelectionItem, SetSelection},
+    selection_code,
+    signing::{self, Keyring, Trust},
+    translation, validation,
+};
+
 use crate::{
     file_handling::LoadedPatchSet,
     patching::{PatchSpecification, ThreadContext},
@@ -33,6 +44,280 @@ fn main() -> eframe::Result {
     )
 }
 
+fn show_patch_entries(
+    ui: &mut egui::Ui,
+    lps: &mut LoadedPatchSet,
+    hide_broken: bool,
+    language: Option<&str>,
+) {
+    let categories = selection::categorised_selection_items(lps.data());
+    let has_headings = categories.iter().any(|c| c.name.is_some());
+
+    for category in categories {
+        // Broken entries that are already enabled stay visible, so they can be turned off
+        let items: Vec<SelectionItem> = category
+            .items
+            .into_iter()
+            .filter(|item| match item {
+                SelectionItem::Entry(i) if hide_broken => {
+                    lps.selection().is_enabled(*i) || !lps.data().entries[*i].status().is_broken()
+                }
+                _ => true,
+            })
+            .collect();
+
+        if items.is_empty() {
+            continue;
+        }
+
+        if has_headings {
+            ui.strong(category.name.as_deref().unwrap_or("Other"));
+            ui.end_row();
+        }
+
+        for item in items {
+            show_selection_item(ui, lps, item, language);
+            ui.end_row();
+        }
+    }
+}
+
+fn show_selection_item(
+    ui: &mut egui::Ui,
+    lps: &mut LoadedPatchSet,
+    item: SelectionItem,
+    language: Option<&str>,
+) {
+    match item {
+        SelectionItem::Entry(i) => {
+            if let Some(patch_entry) = lps.get_patch_entry(i) {
+                ui.horizontal(|ui| {
+                    let mut_bool = &mut lps.enabled_entries_mut()[i];
+                    ui.checkbox(mut_bool, patch_entry.localised_name(language))
+                        .on_hover_text(entry_hover_text(&patch_entry, language));
+
+                    show_entry_status(ui, &patch_entry);
+
+                    show_entry_parameters(ui, lps, i);
+                });
+            }
+        }
+        SelectionItem::Group { group, options } => {
+            option_group_combo_box(ui, lps, &group, &options, language);
+        }
+    }
+}
+
+fn show_entry_status(ui: &mut egui::Ui, entry: &PatchEntry) {
+    let status = entry.status();
+
+    if status.is_stable() {
+        return;
+    }
+
+    let colour = if status.is_broken() {
+        Color32::RED
+    } else {
+        Color32::YELLOW
+    };
+
+    ui.colored_label(colour, status.to_string());
+}
+
+fn entry_hover_text(entry: &PatchEntry, language: Option<&str>) -> String {
+    let mut text = entry.localised_description(language).to_string();
+
+    let mut credits = Vec::new();
+
+    if let Some(version) = entry.version() {
+        credits.push(format!("v{}", version));
+    }
+
+    if let Some(author) = entry.alt_author() {
+        credits.push(format!("by {}", author));
+    }
+
+    if let Some(file) = entry.included_from().and_then(|p| p.file_name()) {
+        credits.push(format!("from {}", file.to_string_lossy()));
+    }
+
+    if !credits.is_empty() {
+        text.push_str(&format!("\n\n{}", credits.join(" ")));
+    }
+
+    if !entry.status().is_stable() {
+        text.push_str(&format!("\n\nStatus: {}", entry.status()));
+    }
+
+    if !entry.known_issues().is_empty() {
+        text.push_str("\n\nKnown issues:");
+
+        for issue in entry.known_issues() {
+            text.push_str(&format!("\n- {}", issue));
+        }
+    }
+
+    if !entry.tags().is_empty() {
+        text.push_str(&format!("\n\nTags: {}", entry.tags().join(", ")));
+    }
+
+    let list = |references: &[EntryReference]| {
+        references
+            .iter()
+            .map(|r| match r.patch_set() {
+                Some(patch_set) => format!("{} ({})", r.entry(), patch_set),
+                None => r.entry().to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+
+    if !entry.requires().is_empty() {
+        text.push_str(&format!("\n\nRequires: {}", list(entry.requires())));
+    }
+
+    if !entry.conflicts().is_empty() {
+        text.push_str(&format!("\n\nConflicts with: {}", list(entry.conflicts())));
+    }
+
+    text
+}
+
+/// Shows a value editor for each parameter of an entry, while the entry is enabled.
+fn show_entry_parameters(ui: &mut egui::Ui, lps: &mut LoadedPatchSet, entry: usize) {
+    let Some(patch_entry) = lps.get_patch_entry(entry) else {
+        return;
+    };
+
+    if !lps.selection().is_enabled(entry) {
+        return;
+    }
+
+    for parameter in patch_entry.parameters() {
+        let mut value = lps.selection().value(entry, parameter);
+
+        let min = parameter.min.unwrap_or(f64::MIN);
+        let max = parameter.max.unwrap_or(f64::MAX);
+
+        let mut drag_value = egui::DragValue::new(&mut value).range(min..=max);
+
+        if parameter.value_type.is_integer() {
+            drag_value = drag_value.speed(1.0).fixed_decimals(0);
+        } else {
+            drag_value = drag_value.speed(0.01);
+        }
+
+        if let Some(unit) = &parameter.unit {
+            drag_value = drag_value.suffix(unit);
+        }
+
+        ui.label(&parameter.name)
+            .on_hover_text(&parameter.description);
+
+        if ui.add(drag_value).changed() {
+            lps.selection_mut().set_value(entry, &parameter.name, value);
+        }
+    }
+}
+
+/// Where the keyring is kept, in the app's config folder so that patch sets can't add themselves
+/// to it.
+fn keyring_path() -> Option<PathBuf> {
+    dirs_next::config_dir().map(|d| d.join("xbpatch").join(signing::KEYRING_FILE_NAME))
+}
+
+/// A badge showing whether a patch set comes from an author in the keyring.
+fn show_trust_badge(ui: &mut egui::Ui, trust: &Trust) {
+    let (colour, text) = match trust {
+        Trust::Trusted(_) => (Color32::GREEN, "Trusted"),
+        Trust::Untrusted(_) => (Color32::YELLOW, "Untrusted"),
+        Trust::Unsigned => (Color32::GRAY, "Unsigned"),
+        Trust::Tampered(_) => (Color32::RED, "Tampered"),
+    };
+
+    ui.colored_label(colour, text)
+        .on_hover_text(trust.to_string());
+}
+
+/// Lets the user pick which of a patch set's translations to show.
+fn language_combo_box(ui: &mut egui::Ui, language: &mut Option<String>, languages: &[String]) {
+    ui.horizontal(|ui| {
+        ui.label("Language");
+
+        egui::ComboBox::from_id_salt("language")
+            .selected_text(language.as_deref().unwrap_or("Default"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(language, None, "Default");
+
+                for code in languages {
+                    ui.selectable_value(language, Some(code.clone()), code);
+                }
+            });
+    });
+}
+
+/// Shows the entries of an option group as a single choice, where picking one option disables the
+/// others.
+fn option_group_combo_box(
+    ui: &mut egui::Ui,
+    lps: &mut LoadedPatchSet,
+    group: &OptionGroup,
+    options: &[usize],
+    language: Option<&str>,
+) {
+    let none_text = match group.selection {
+        OptionGroupSelection::AtMostOne => "(None)",
+        OptionGroupSelection::ExactlyOne => "(Choose one)",
+    };
+
+    let mut chosen = options
+        .iter()
+        .find(|i| lps.enabled_entries().get(**i).cloned().unwrap_or(false))
+        .cloned();
+
+    let chosen_text = chosen
+        .and_then(|i| lps.get_patch_entry(i))
+        .map(|e| e.localised_name(language).to_string())
+        .unwrap_or(none_text.to_string());
+
+    ui.horizontal(|ui| {
+        ui.label(&group.name).on_hover_text(&group.description);
+
+        egui::ComboBox::from_id_salt(("option_group", &group.name))
+            .selected_text(chosen_text)
+            .show_ui(ui, |ui| {
+                if group.selection == OptionGroupSelection::AtMostOne {
+                    ui.selectable_value(&mut chosen, None, none_text);
+                }
+
+                for i in options {
+                    if let Some(entry) = lps.get_patch_entry(*i) {
+                        let name = entry.localised_name(language);
+
+                        let text = if entry.status().is_stable() {
+                            name.to_string()
+                        } else {
+                            format!("{} ({})", name, entry.status())
+                        };
+
+                        ui.selectable_value(&mut chosen, Some(*i), text)
+                            .on_hover_text(entry_hover_text(&entry, language));
+                    }
+                }
+            });
+
+        for i in options {
+            if let Some(enabled) = lps.enabled_entries_mut().get_mut(*i) {
+                *enabled = chosen == Some(*i);
+            }
+        }
+
+        if let Some(i) = chosen {
+            show_entry_parameters(ui, lps, i);
+        }
+    });
+}
+
 enum ISOStatus {
     Valid,
     UnrecognisedFiletype,
@@ -48,6 +333,8 @@ enum XBPatchAppStatus {
     GettingNewPatchSetName,
     DeletionPrompt,
     SelectedInputISO,
+    ShowingError,
+    ShowingMessage,
 
     // Patch related
     ConfirmingPatch,
@@ -66,6 +353,8 @@ struct XBPatchApp {
 
     iso_finder_dialog: Option<FileDialog>,
     iso_finder: Option<PathBuf>,
+    xbe_finder_dialog: Option<FileDialog>,
+    bundle_finder_dialog: Option<FileDialog>,
 
     cwd_path: PathBuf,
     extract_xiso_path: String,
@@ -84,12 +373,27 @@ struct XBPatchApp {
 
     patch_sets_path: Option<PathBuf>,
     loaded_patches: Vec<LoadedPatchSet>,
+    // The authors whose signed patch sets are trusted
+    keyring: Keyring,
     current_patch_set: u32,
+    hide_broken_entries: bool,
+    // The language to show names and descriptions in, or None for the untranslated text
+    language: Option<String>,
+    // The seed to pick random values with, or empty to pick with a new one
+    seed_input: String,
+    // A selection code to share, or one pasted in to choose entries with
+    selection_code_input: String,
     modal_input: String,
+    error_message: String,
+    // Shown to the user once something has finished, eg. how many original bytes were filled in
+    message: String,
 
     thread_context: Arc<RwLock<ThreadContext>>,
 
     force_reextract: bool,
+
+    // Patch with patch sets that have changed since they were signed, instead of refusing to
+    allow_tampered: bool,
 }
 
 impl Default for XBPatchApp {
@@ -123,16 +427,26 @@ impl Default for XBPatchApp {
             // Patch set folder
             patch_sets_path,
             loaded_patches: Vec::new(),
+            keyring: Keyring::default(),
             current_patch_set: 0,
+            hide_broken_entries: false,
+            language: translation::user_language(),
+            seed_input: String::new(),
+            selection_code_input: String::new(),
             modal_input: String::new(),
+            error_message: String::new(),
+            message: String::new(),
             iso_finder_dialog: None,
             iso_finder: None,
+            xbe_finder_dialog: None,
+            bundle_finder_dialog: None,
 
             patch_specification: None,
             patch_progress: PatchProgress::Success,
 
             thread_context: Arc::new(RwLock::new(Default::default())),
             force_reextract: false,
+            allow_tampered: false,
         }
     }
 }
@@ -185,12 +499,15 @@ impl XBPatchApp {
                     }
                 };
 
-                self.status = XBPatchAppStatus::Normal
+                // Unless reloading found patch sets that can't be loaded
+                if self.status == XBPatchAppStatus::Startup {
+                    self.status = XBPatchAppStatus::Normal
+                }
             }
             XBPatchAppStatus::NeedReload => {
+                self.status = XBPatchAppStatus::Normal;
                 // TODO: Handle failure here
                 self.reload_patch_sets();
-                self.status = XBPatchAppStatus::Normal;
             }
             XBPatchAppStatus::Normal => {}
             XBPatchAppStatus::GettingNewPatchSetName => {
@@ -208,6 +525,9 @@ impl XBPatchApp {
                             if ui.button("OK").clicked() {
                                 println!("User entered new patch set: {}", &self.modal_input);
 
+                                // Reloading shows an error if any patch sets can't be loaded
+                                self.status = XBPatchAppStatus::Normal;
+
                                 if let Some(p) = &self.patch_sets_path {
                                     let new_path = p.join(new_filename);
 
@@ -231,8 +551,6 @@ impl XBPatchApp {
                                         }
                                     };
                                 };
-
-                                self.status = XBPatchAppStatus::Normal;
                             }
                             if ui.button("Cancel").clicked() {
                                 self.status = XBPatchAppStatus::Normal;
@@ -304,6 +622,16 @@ impl XBPatchApp {
 
                 self.status = XBPatchAppStatus::Normal;
             }
+            XBPatchAppStatus::ShowingError => {
+                modals::error_prompt(ctx, "error_message", &self.error_message, || {
+                    self.status = XBPatchAppStatus::Normal;
+                });
+            }
+            XBPatchAppStatus::ShowingMessage => {
+                modals::message_prompt(ctx, "message", &self.message, || {
+                    self.status = XBPatchAppStatus::Normal;
+                });
+            }
             XBPatchAppStatus::ConfirmingPatch => {
                 if let Some(spec) = &self.patch_specification {
                     let text = format!(
@@ -323,7 +651,15 @@ Patches:
                         &spec
                             .entries()
                             .iter()
-                            .map(|e| { format!("- {}", e.name()) })
+                            .map(|e| if e.status().is_stable() {
+                                format!("- {}", e.localised_name(self.language.as_deref()))
+                            } else {
+                                format!(
+                                    "- {} (WARNING: {})",
+                                    e.localised_name(self.language.as_deref()),
+                                    e.status()
+                                )
+                            })
                             .collect::<Vec<String>>()
                             .join("\n")
                     );
@@ -358,11 +694,20 @@ Patches:
 
                         let mut string;
                         let finished;
+                        let question;
 
                         {
                             let ctx = self.thread_context.read().unwrap();
                             string = String::from(ctx.log());
                             finished = ctx.completed() || ctx.error();
+                            question = ctx.question().cloned();
+                        }
+
+                        // Patching waits until the question is answered
+                        if let Some(question) = &question {
+                            modals::ask_user(ui.ctx(), "patching_question", question, |b| {
+                                self.thread_context.write().unwrap().answer(b);
+                            });
                         }
 
                         egui::ScrollArea::vertical()
@@ -423,11 +768,27 @@ Patches:
                 }
 
                 self.loaded_patches.clear();
+                let mut failures = Vec::new();
+
+                self.keyring = match keyring_path() {
+                    Some(path) => Keyring::from_file(&path).unwrap_or_else(|e| {
+                        eprintln!(
+                            "Unable to load the keyring, so no patch sets are trusted. {}",
+                            e
+                        );
+                        Keyring::default()
+                    }),
+                    None => Keyring::default(),
+                };
 
                 for entry in walkdir::WalkDir::new(p)
                     .into_iter()
                     .filter_map(Result::ok)
                     .filter(|e| e.path().is_file())
+                    // Files that patch sets copy into the game are kept alongside them
+                    .filter(|e| e.path().extension() == Some(OsStr::new("json")))
+                    // The JSON Schema is shipped alongside the patch sets, but isn't one itself
+                    .filter(|e| e.file_name() != schema::JSON_SCHEMA_FILE_NAME)
                 {
                     if entry.path().is_dir() {
                         continue;
@@ -437,20 +798,32 @@ Patches:
 
                     println!("Importing PatchSet from {}", path_buf.display());
 
-                    let new_lps = match LoadedPatchSet::existing(&path_buf) {
+                    let new_lps = match LoadedPatchSet::existing(&path_buf, &self.keyring) {
                         Ok(l) => l,
                         Err(e) => {
-                            println!(
-                                "Unable to load PatchSet from {}\nDetails: {}",
-                                path_buf.display(),
-                                e
-                            );
+                            let mut failure = format!("{}\nDetails: {}", path_buf.display(), e);
+
+                            for problem in validation::validate_file(&path_buf).unwrap_or_default()
+                            {
+                                failure.push_str(&format!("\n    {}", problem));
+                            }
+
+                            failures.push(failure);
                             continue;
                         }
                     };
 
                     self.loaded_patches.push(new_lps);
                 }
+
+                if !failures.is_empty() {
+                    self.error_message = format!(
+                        "Unable to load {} patch sets.\n\n{}",
+                        failures.len(),
+                        failures.join("\n\n")
+                    );
+                    self.status = XBPatchAppStatus::ShowingError;
+                }
             }
             None => {
                 eprintln!("Unable to reload patch sets when one hasn't been chosen.");
@@ -460,6 +833,81 @@ Patches:
         Ok(())
     }
 
+    fn fill_original_bytes(&mut self, xbe_path: &PathBuf) {
+        let Some(lps) = self.loaded_patches.get_mut(self.current_patch_set as usize) else {
+            return;
+        };
+
+        match lps.fill_original_bytes(xbe_path, &self.keyring) {
+            Ok(filled) => {
+                self.message = format!(
+                    "Filled in the original bytes of {} patches in {}.",
+                    filled,
+                    lps.path().display()
+                );
+                self.status = XBPatchAppStatus::ShowingMessage;
+            }
+            Err(e) => {
+                self.error_message = format!(
+                    "Unable to fill original bytes from {}\nError: {}",
+                    xbe_path.display(),
+                    e
+                );
+                self.status = XBPatchAppStatus::ShowingError;
+            }
+        };
+    }
+
+    /// Extracts a bundle into its own folder among the patch sets, then reloads them.
+    fn import_bundle(&mut self, bundle_path: &PathBuf) {
+        let Some(patch_sets_path) = &self.patch_sets_path else {
+            eprintln!("Unable to import a bundle when no patch set folder has been chosen.");
+            return;
+        };
+
+        let folder = patch_sets_path.join(bundle_path.file_stem().unwrap_or_default());
+
+        match PatchBundle::open(bundle_path).and_then(|mut b| b.extract(&folder)) {
+            Ok(patch_set_path) => {
+                println!("Imported {}", patch_set_path.display());
+                self.status = XBPatchAppStatus::NeedReload;
+            }
+            Err(e) => {
+                self.error_message =
+                    format!("Unable to import {}\nError: {}", bundle_path.display(), e);
+                self.status = XBPatchAppStatus::ShowingError;
+            }
+        };
+    }
+
+    /// A code for the entries enabled in every loaded patch set, for sharing with other players.
+    fn selection_code(&self) -> String {
+        let selections: Vec<(&PatchSet, &SetSelection)> = self
+            .loaded_patches
+            .iter()
+            .map(|lps| (lps.data(), lps.selection()))
+            .collect();
+
+        selection_code::encode(&selections)
+    }
+
+    /// Replaces what is enabled in every loaded patch set with the entries in the pasted code.
+    fn use_selection_code(&mut self) {
+        let patch_sets: Vec<&PatchSet> = self.loaded_patches.iter().map(|lps| lps.data()).collect();
+
+        match selection_code::decode(&self.selection_code_input, &patch_sets) {
+            Ok(selections) => {
+                for (lps, selection) in self.loaded_patches.iter_mut().zip(selections) {
+                    *lps.selection_mut() = selection;
+                }
+            }
+            Err(e) => {
+                self.error_message = format!("Unable to use the selection code.\n{}", e);
+                self.status = XBPatchAppStatus::ShowingError;
+            }
+        };
+    }
+
     pub fn create_patch_spec(&self) -> Result<PatchSpecification, Box<dyn std::error::Error>> {
         let spec = PatchSpecification::from_xbpatchapp(self)?;
         Ok(spec)
@@ -492,6 +940,52 @@ impl eframe::App for XBPatchApp {
             egui::SidePanel::right("patching_panel")
                 .max_width(0.45 * width)
                 .show_inside(ui, |ui| {
+                    if self.has_current_patch_set() {
+                        if ui
+                            .button("Fill original bytes")
+                            .on_hover_text("Read the original bytes of every patch in this patch set from a clean default.xbe, and save them to the patch set.")
+                            .clicked()
+                            && self.status == XBPatchAppStatus::Normal
+                        {
+                            let filter = Box::new(|path: &Path| -> bool {
+                                path.extension() == Some(OsStr::new("xbe"))
+                            });
+                            let mut dialog = FileDialog::open_file(None)
+                                .show_files_filter(filter)
+                                .title("Select a clean default.xbe");
+
+                            dialog.open();
+                            self.xbe_finder_dialog = Some(dialog);
+                        }
+
+                        if let Some(dialog) = &mut self.xbe_finder_dialog
+                            && dialog.show(ctx).selected()
+                        {
+                            let xbe_path = dialog.path().map(PathBuf::from);
+                            self.xbe_finder_dialog = None;
+
+                            if let Some(xbe_path) = xbe_path {
+                                self.fill_original_bytes(&xbe_path);
+                            }
+                        }
+                    }
+
+                    ui.checkbox(&mut self.hide_broken_entries, "Hide broken entries")
+                        .on_hover_text("Hide entries that are known to be broken or to cause softlocks.");
+
+                    let hide_broken = self.hide_broken_entries;
+
+                    if let Some(lps) = self.current_loaded_patch_set() {
+                        let languages: Vec<String> =
+                            lps.data().languages().into_iter().cloned().collect();
+
+                        if !languages.is_empty() {
+                            language_combo_box(ui, &mut self.language, &languages);
+                        }
+                    }
+
+                    let language = self.language.clone();
+
                     if let Some(mut_lps) = self.current_loaded_patch_set_mut() {
                         /*
                         if lps.data().len() == 0 {
@@ -514,18 +1008,12 @@ impl eframe::App for XBPatchApp {
                                     .spacing([10.0, 4.0])
                                     .striped(false)
                                     .show(ui, |ui| {
-                                        let len = mut_lps.enabled_entries_mut().len();
-
-                                        for i in 0..len {
-                                            if let Some(patch_entry) = mut_lps.get_patch_entry(i) {
-                                                let mut_bool =
-                                                    &mut mut_lps.enabled_entries_mut()[i];
-                                                ui.checkbox(mut_bool, patch_entry.name())
-                                                    .on_hover_text(patch_entry.description());
-
-                                                ui.end_row();
-                                            }
-                                        }
+                                        show_patch_entries(
+                                            ui,
+                                            mut_lps,
+                                            hide_broken,
+                                            language.as_deref(),
+                                        );
                                     });
                             });
                     } else {
@@ -538,12 +1026,40 @@ impl eframe::App for XBPatchApp {
                 .show_inside(ui, |ui| {
                     ui.heading("Patch Sets");
 
+                    if ui
+                        .button("Import bundle")
+                        .on_hover_text("Add the patch set from a .xbpatch bundle, along with its files.")
+                        .clicked()
+                        && self.status == XBPatchAppStatus::Normal
+                    {
+                        let filter = Box::new(|path: &Path| -> bool {
+                            path.extension() == Some(OsStr::new(bundle::BUNDLE_EXTENSION))
+                        });
+                        let mut dialog = FileDialog::open_file(None)
+                            .show_files_filter(filter)
+                            .title("Select a patch bundle");
+
+                        dialog.open();
+                        self.bundle_finder_dialog = Some(dialog);
+                    }
+
+                    if let Some(dialog) = &mut self.bundle_finder_dialog
+                        && dialog.show(ctx).selected()
+                    {
+                        let bundle_path = dialog.path().map(PathBuf::from);
+                        self.bundle_finder_dialog = None;
+
+                        if let Some(bundle_path) = bundle_path {
+                            self.import_bundle(&bundle_path);
+                        }
+                    }
+
                     egui::ScrollArea::vertical()
                         .auto_shrink(true)
                         .show(ui, |ui| {
                             ui.horizontal(|ui| {
                                 egui::Grid::new("patch_sets")
-                                    .num_columns(2)
+                                    .num_columns(3)
                                     .spacing([40.0, 4.0])
                                     .striped(false)
                                     .show(ui, |ui| {
@@ -571,9 +1087,16 @@ impl eframe::App for XBPatchApp {
                                                 patch_set.len()
                                             );
                                             ui.label(label_text);
-                                            if ui.label(&patch_set.name).clicked() {
+                                            if ui
+                                                .label(
+                                                    patch_set
+                                                        .localised_name(self.language.as_deref()),
+                                                )
+                                                .clicked()
+                                            {
                                                 self.current_patch_set = i as u32;
                                             };
+                                            show_trust_badge(ui, lps.trust());
                                             ui.end_row();
                                         }
                                     });
@@ -737,9 +1260,45 @@ impl eframe::App for XBPatchApp {
                 };
             });
 
+            ui.horizontal(|ui| {
+                ui.heading("Random Seed");
+                ui.group(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.seed_input).hint_text("New seed"))
+                        .on_hover_text("The seed random values are picked with. Enter the seed from an earlier patch to make the same build again.");
+                });
+            });
+
+            ui.horizontal(|ui| {
+                ui.heading("Selection Code");
+                ui.group(|ui| {
+                    ui.add(TextEdit::singleline(&mut self.selection_code_input).hint_text(selection_code::CODE_PREFIX));
+
+                    if ui
+                        .button("Copy")
+                        .on_hover_text("Make a code for the entries enabled in every patch set, and copy it to share.")
+                        .clicked()
+                    {
+                        self.selection_code_input = self.selection_code();
+                        ui.ctx().copy_text(self.selection_code_input.clone());
+                    }
+
+                    if ui
+                        .button("Use")
+                        .on_hover_text("Enable the entries in the code, replacing the entries enabled now.")
+                        .clicked()
+                        && self.status == XBPatchAppStatus::Normal
+                    {
+                        self.use_selection_code();
+                    }
+                });
+            });
+
             ui.checkbox(&mut self.force_reextract, "Force re-extraction")
                 .on_hover_text("Force a re-extraction of an iso to occur when its contents have already been extracted in a previous patch. Useful if the files in the extraction become corrupt.");
 
+            ui.checkbox(&mut self.allow_tampered, "Allow tampered patch sets")
+                .on_hover_text("Patch with patch sets that have changed since they were signed. Only use this if you know why they changed.");
+
             if ui.button("Patch").clicked() {
                 if self.status == XBPatchAppStatus::Normal {
                     match self.create_patch_spec() {
@@ -748,7 +1307,8 @@ impl eframe::App for XBPatchApp {
                             self.status = XBPatchAppStatus::ConfirmingPatch;
                         }
                         Err(e) => {
-                            eprintln!("Unable to create patch spec.\nError: {}", e);
+                            self.error_message = format!("Unable to create patch spec.\n{}", e);
+                            self.status = XBPatchAppStatus::ShowingError;
                         }
                     };
                 }
@@ -38,3 +38,20 @@ where
             }
         });
 }
+
+pub fn message_prompt<F>(ctx: &Context, id: &str, message: &String, on_answer: F)
+where
+    F: FnOnce(),
+{
+    Modal::new(Id::new(id))
+        .backdrop_color(Color32::from_black_alpha(100))
+        .show(ctx, |ui| {
+            ui.label(message);
+
+            ui.add_space(20.0);
+
+            if ui.button("OK").clicked() {
+                on_answer()
+            }
+        });
+}
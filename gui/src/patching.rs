@@ -2,12 +2,17 @@ use std::{
     ffi::OsStr,
     path::PathBuf,
     sync::{Arc, RwLock},
+    time::Duration,
 };
 
 use walkdir::WalkDir;
 use xbpatch_core::{
+    game_files::GameFiles,
     iso_handling::{self, backup_file, restore_backup},
-    patching::PatchEntry,
+    patching::{
+        PatchEntry, PatchSet, job::PatchJob, selection::SetSelection, signing::Trust,
+        target::TargetMatch,
+    },
     xbe::{PatchReport, XBEWriter},
 };
 
@@ -23,6 +28,12 @@ pub struct PatchSpecification {
 
     entries: Vec<PatchEntry>,
     force_reextract: bool,
+
+    // The patch sets that entries were chosen from, to check against the XBE
+    patch_sets: Vec<PatchSet>,
+
+    // The seed random values were picked with, if any entries have them
+    seed: Option<u64>,
 }
 
 impl PatchSpecification {
@@ -34,21 +45,73 @@ impl PatchSpecification {
             entries: Vec::new(),
             force_reextract: app.force_reextract,
             extract_xiso_path: Default::default(),
+            patch_sets: Vec::new(),
+            seed: None,
         };
 
-        for loaded_set in app.loaded_patch_sets() {
-            let enabled_entries = loaded_set.enabled_entries();
+        // Entries from a patch set that has changed since it was signed could do anything
+        let tampered: Vec<String> = app
+            .loaded_patch_sets()
+            .iter()
+            .filter(|lps| lps.selection().enabled.contains(&true))
+            .filter_map(|lps| match lps.trust() {
+                Trust::Tampered(reason) => Some(format!("- {}: {}", lps.data().name, reason)),
+                _ => None,
+            })
+            .collect();
+
+        if !tampered.is_empty() && !app.allow_tampered {
+            return Err(format!(
+                "These patch sets have been tampered with since they were signed, so their entries won't be applied. Tick \"Allow tampered patch sets\" to use them anyway:\n{}",
+                tampered.join("\n")
+            )
+            .into());
+        }
 
-            for (i, _) in loaded_set
-                .patch_set
-                .entries
-                .iter()
-                .enumerate()
-                .filter(|(i, _v)| enabled_entries.get(*i).cloned().unwrap_or(false))
-            {
-                spec.entries.push(loaded_set.patch_set.entries[i].clone());
+        let selections: Vec<(&PatchSet, &SetSelection)> = app
+            .loaded_patch_sets()
+            .iter()
+            .map(|lps| (lps.data(), lps.selection()))
+            .collect();
+
+        let seed_input = app.seed_input.trim();
+
+        let job = if seed_input.is_empty() {
+            PatchJob::new(&selections)
+        } else {
+            match seed_input.parse::<u64>() {
+                Ok(seed) => PatchJob::with_seed(&selections, seed),
+                Err(_) => {
+                    return Err(
+                        format!("The seed \"{}\" is not a whole number.", seed_input).into(),
+                    );
+                }
             }
-        }
+        };
+
+        spec.entries = match job {
+            Ok(job) => {
+                spec.seed = job.seed();
+                job.into_entries()
+            }
+            Err(errors) => {
+                return Err(format!(
+                    "The chosen patches can't be applied together:\n{}",
+                    errors
+                        .iter()
+                        .map(|e| format!("- {}", e))
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                )
+                .into());
+            }
+        };
+
+        spec.patch_sets = selections
+            .iter()
+            .filter(|(_, selection)| selection.enabled.contains(&true))
+            .map(|(patch_set, _)| (*patch_set).clone())
+            .collect();
 
         spec.in_file = PathBuf::from(&app.input_iso_path);
         spec.out_file = PathBuf::from(&app.output_iso_path);
@@ -89,6 +152,10 @@ pub struct ThreadContext {
     completed: bool,
     error: bool,
     log: String,
+
+    // A question the patching thread is waiting on the user to answer
+    question: Option<String>,
+    answer: Option<bool>,
 }
 
 impl ThreadContext {
@@ -103,15 +170,43 @@ impl ThreadContext {
     pub fn error(&self) -> bool {
         self.error
     }
+
+    pub fn question(&self) -> Option<&String> {
+        self.question.as_ref()
+    }
+
+    pub fn answer(&mut self, answer: bool) {
+        self.question = None;
+        self.answer = Some(answer);
+    }
 }
 
-pub fn patch_iso_thread(ctx_lock: Arc<RwLock<ThreadContext>>, spec: PatchSpecification) {
+// Waits for the user to answer a question shown in the patching window
+fn ask_user(ctx_lock: &Arc<RwLock<ThreadContext>>, question: String) -> bool {
+    {
+        let mut ctx = ctx_lock.write().unwrap();
+        ctx.question = Some(question);
+        ctx.answer = None;
+    }
+
+    loop {
+        std::thread::sleep(Duration::from_millis(50));
+
+        if let Some(answer) = ctx_lock.write().unwrap().answer.take() {
+            return answer;
+        }
+    }
+}
+
+pub fn patch_iso_thread(ctx_lock: Arc<RwLock<ThreadContext>>, mut spec: PatchSpecification) {
     {
         let mut ctx = ctx_lock.write().unwrap();
 
         ctx.log.clear();
         ctx.completed = false;
         ctx.error = false;
+        ctx.question = None;
+        ctx.answer = None;
     }
 
     // Helper functions for printing
@@ -140,10 +235,11 @@ pub fn patch_iso_thread(ctx_lock: Arc<RwLock<ThreadContext>>, spec: PatchSpecifi
         ),
     );
 
-    let extraction_path: PathBuf = spec
-        .temp_folder
-        .join("isos")
-        .join(spec.in_file().file_stem().unwrap_or(OsStr::new("temp")));
+    let iso_name = spec.in_file().file_stem().unwrap_or(OsStr::new("temp"));
+    let extraction_path: PathBuf = spec.temp_folder.join("isos").join(iso_name);
+
+    // Kept outside the extracted game, so they aren't packed into the new iso
+    let backup_path: PathBuf = spec.temp_folder.join("backups").join(iso_name);
 
     if extraction_path.exists() && extraction_path.is_dir() && spec.force_reextract {
         ctx_print(
@@ -151,6 +247,10 @@ pub fn patch_iso_thread(ctx_lock: Arc<RwLock<ThreadContext>>, spec: PatchSpecifi
             format!("Deleting existing directory {}", extraction_path.display()),
         );
         std::fs::remove_dir_all(&extraction_path).expect("Unable to delete existing folder.");
+
+        if backup_path.exists() {
+            std::fs::remove_dir_all(&backup_path).expect("Unable to delete existing backups.");
+        }
     }
 
     // Only extract if the folder doesn't exist
@@ -210,7 +310,7 @@ pub fn patch_iso_thread(ctx_lock: Arc<RwLock<ThreadContext>>, spec: PatchSpecifi
     }
 
     // Parse the config
-    let mut xbe_writer = match XBEWriter::new(&xbe_path) {
+    let xbe_writer = match XBEWriter::new(&xbe_path) {
         Ok(w) => w,
         Err(_) => {
             ctx_error(
@@ -225,7 +325,101 @@ pub fn patch_iso_thread(ctx_lock: Arc<RwLock<ThreadContext>>, spec: PatchSpecifi
         }
     };
 
+    let identity = match xbe_writer.identify() {
+        Ok(i) => i,
+        Err(e) => {
+            ctx_error(
+                &ctx_lock,
+                format!("Unable to read default.xbe. Error: {}", e),
+            );
+            return;
+        }
+    };
+
+    for patch_set in &spec.patch_sets {
+        match patch_set.check_targets(&identity) {
+            TargetMatch::Matches => (),
+            TargetMatch::UnknownBuild(reason) => {
+                ctx_print(&ctx_lock, format!("WARNING: {}.", reason));
+            }
+            TargetMatch::WrongGame(reason) => {
+                ctx_error(
+                    &ctx_lock,
+                    format!("{}. Refusing to patch a different game.", reason),
+                );
+                return;
+            }
+        }
+    }
+
     let mut report = PatchReport::default();
+    report.set_seed(spec.seed);
+
+    // Entries with nothing to apply to this build are left out, and count as failures
+    spec.entries
+        .retain_mut(|entry| match entry.select_build(&identity) {
+            Ok(true) => true,
+            Ok(false) => {
+                ctx_print(
+                    &ctx_lock,
+                    format!(
+                        "WARNING: \"{}\" has no patches specific to this build of the game, so its usual patches will be used.",
+                        entry.name()
+                    ),
+                );
+                true
+            }
+            Err(e) => {
+                ctx_print(&ctx_lock, format!("FAILED: {}", e));
+                report.add_failure();
+                false
+            }
+        });
+
+    let mut game_files = match GameFiles::open(&xbe_path, xbe_writer, &spec.entries, &backup_path) {
+        Ok(g) => g,
+        Err(e) => {
+            ctx_error(
+                &ctx_lock,
+                format!("Unable to open the game files to patch. Error: {}", e),
+            );
+            return;
+        }
+    };
+
+    match game_files.find_overlaps(&spec.entries) {
+        Ok(overlaps) => {
+            for overlap in &overlaps {
+                ctx_print(&ctx_lock, format!("WARNING: {}.", overlap));
+            }
+
+            if !overlaps.is_empty()
+                && !ask_user(
+                    &ctx_lock,
+                    "Some entries overwrite each other, and the later entry will win. Would you like to continue?"
+                        .to_string(),
+                )
+            {
+                ctx_error(&ctx_lock, String::from("Cancelled."));
+                return;
+            }
+        }
+        Err(e) => {
+            ctx_error(
+                &ctx_lock,
+                format!("Unable to resolve patch offsets. Error: {}", e),
+            );
+            return;
+        }
+    };
+
+    if let Err(e) = game_files.apply_file_changes() {
+        ctx_error(
+            &ctx_lock,
+            format!("Unable to change the game's files. Error: {}", e),
+        );
+        return;
+    }
 
     for entry in spec.entries {
         ctx_print(
@@ -233,7 +427,7 @@ pub fn patch_iso_thread(ctx_lock: Arc<RwLock<ThreadContext>>, spec: PatchSpecifi
             format!("Applying patch \"{}\"...  ", entry.name()),
         );
 
-        match xbe_writer.apply_patches(&entry) {
+        match game_files.apply_patches(&entry) {
             Ok(patch_report) => {
                 if patch_report.patch_successful() {
                     report.add_success();
@@ -255,6 +449,16 @@ pub fn patch_iso_thread(ctx_lock: Arc<RwLock<ThreadContext>>, spec: PatchSpecifi
         }
     }
 
+    if let Some(seed) = report.seed() {
+        ctx_print(
+            &ctx_lock,
+            format!(
+                "Random values were picked with seed {}. Enter it as the random seed to make this build again.",
+                seed
+            ),
+        );
+    }
+
     if report.failures() == 0 {
         println!("All patches applied successfully.");
     } else if report.successes() == 0 {